@@ -0,0 +1,472 @@
+use crate::op_codes::OpCode;
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt, fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+static MAX_MACRO_DEPTH: usize = 64;
+
+/// A problem found while assembling, and where it was found.
+#[derive(Clone, Debug)]
+pub struct AssembleError {
+    /// The source file.
+    pub path: PathBuf,
+    /// The line in the source file, starting at 1.
+    pub line: usize,
+    /// What went wrong.
+    pub message: String,
+}
+
+impl fmt::Display for AssembleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.path.display(), self.line, self.message)
+    }
+}
+
+impl Error for AssembleError {}
+
+/// A label and the address it was given.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Label {
+    /// The name, including any `@parent/` scope.
+    pub name: String,
+    /// The address the label points to.
+    pub addr: u16,
+}
+
+/// The output of the assembler.
+#[derive(Clone, Debug)]
+pub struct Assembly {
+    /// The ROM, starting at `0x0100`.
+    pub rom: Vec<u8>,
+    /// Every label, in the order it was defined.
+    pub labels: Vec<Label>,
+}
+
+impl Assembly {
+    /// The labels as a symbol file, a big-endian address followed by a null-terminated name
+    /// for each label.
+    pub fn symbol_file(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        for label in self.labels.iter() {
+            bytes.extend(label.addr.to_be_bytes());
+            bytes.extend(label.name.as_bytes());
+            bytes.push(0x00);
+        }
+        bytes
+    }
+}
+
+#[derive(Clone)]
+struct Token {
+    word: String,
+    path: Rc<PathBuf>,
+    line: usize,
+}
+
+impl Token {
+    fn error(&self, message: impl fmt::Display) -> AssembleError {
+        AssembleError {
+            path: PathBuf::from(self.path.as_ref()),
+            line: self.line,
+            message: format!("{message}: {}", self.word),
+        }
+    }
+}
+
+struct Reference {
+    name: String,
+    rune: char,
+    addr: u16,
+    token: Token,
+}
+
+fn tokenize(source: &str, path: &Path) -> Vec<Token> {
+    let path = Rc::new(path.to_path_buf());
+    source
+        .lines()
+        .enumerate()
+        .flat_map(|(index, line)| {
+            let path = Rc::clone(&path);
+            line.split_whitespace().map(move |word| Token {
+                word: word.to_string(),
+                path: Rc::clone(&path),
+                line: index + 1,
+            })
+        })
+        .collect()
+}
+
+/// Tracks nested comments, returning whether `word` is part of one.
+fn skip_comment(word: &str, depth: &mut usize) -> bool {
+    if *depth == 0 && !word.starts_with('(') {
+        return false;
+    }
+    if word.starts_with('(') {
+        *depth += 1;
+    }
+    if word.ends_with(')') {
+        *depth -= 1;
+    }
+    true
+}
+
+fn parse_hex(word: &str) -> Option<u16> {
+    let is_hex = word
+        .chars()
+        .all(|ch| ch.is_ascii_digit() || ('a'..='f').contains(&ch));
+    if is_hex && (1..=4).contains(&word.len()) {
+        u16::from_str_radix(word, 16).ok()
+    } else {
+        None
+    }
+}
+
+fn parse_op_code(word: &str) -> Option<u8> {
+    if !word.is_char_boundary(3) {
+        return None;
+    }
+    let (name, modes) = word.split_at(3);
+    let mut byte = match name {
+        "BRK" if modes.is_empty() => return Some(0x00),
+        "LIT" => 0x80,
+        _ => (0x01..0x20).find(|byte| OpCode::from(*byte).to_string() == name)?,
+    };
+    for mode in modes.chars() {
+        let flag = match mode {
+            '2' => 1 << 5,
+            'r' => 1 << 6,
+            'k' => 1 << 7,
+            _ => return None,
+        };
+        if byte & flag != 0 && !(name == "LIT" && mode == 'k') {
+            return None;
+        }
+        byte |= flag;
+    }
+    Some(byte)
+}
+
+struct Assembler {
+    data: Vec<u8>,
+    ptr: usize,
+    length: usize,
+    scope: String,
+    labels: Vec<Label>,
+    label_addrs: HashMap<String, u16>,
+    macros: HashMap<String, Vec<Token>>,
+    references: Vec<Reference>,
+    depth: usize,
+    /// The files being assembled, the outermost first, to catch files that include themselves.
+    includes: Vec<PathBuf>,
+}
+
+impl Default for Assembler {
+    fn default() -> Assembler {
+        Assembler {
+            data: vec![0; 0x10000],
+            ptr: 0,
+            length: 0x100,
+            scope: String::new(),
+            labels: vec![],
+            label_addrs: HashMap::new(),
+            macros: HashMap::new(),
+            references: vec![],
+            depth: 0,
+            includes: vec![],
+        }
+    }
+}
+
+impl Assembler {
+    fn write_u8(&mut self, byte: u8, token: &Token) -> Result<(), AssembleError> {
+        if self.ptr < 0x100 {
+            return Err(token.error("Writing in zero-page"));
+        }
+        if self.ptr > 0xffff {
+            return Err(token.error("Writing past the end of memory"));
+        }
+        self.data[self.ptr] = byte;
+        self.ptr += 1;
+        self.length = self.length.max(self.ptr);
+        Ok(())
+    }
+
+    fn write_u16(&mut self, short: u16, token: &Token) -> Result<(), AssembleError> {
+        let [high, low] = short.to_be_bytes();
+        self.write_u8(high, token)?;
+        self.write_u8(low, token)
+    }
+
+    fn full_name(&self, name: &str) -> String {
+        match name.strip_prefix('&') {
+            Some(sublabel) => format!("{}/{sublabel}", self.scope),
+            None => name.to_string(),
+        }
+    }
+
+    fn define_label(&mut self, name: String, token: &Token) -> Result<(), AssembleError> {
+        if name.is_empty() || name.ends_with('/') {
+            return Err(token.error("Invalid label name"));
+        }
+        if matches!(name.len(), 2 | 4) && parse_hex(&name).is_some() {
+            return Err(token.error("Label name is a hex number"));
+        }
+        if parse_op_code(&name).is_some() {
+            return Err(token.error("Label name is an opcode"));
+        }
+        if self.macros.contains_key(&name) || self.label_addrs.contains_key(&name) {
+            return Err(token.error("Label is already defined"));
+        }
+        if self.ptr > 0xffff {
+            return Err(token.error("Label is past the end of memory"));
+        }
+        let addr = self.ptr as u16;
+        self.label_addrs.insert(name.clone(), addr);
+        self.labels.push(Label { name, addr });
+        Ok(())
+    }
+
+    fn add_reference(&mut self, name: &str, rune: char, token: &Token) {
+        let name = self.full_name(name);
+        self.references.push(Reference {
+            name,
+            rune,
+            addr: self.ptr as u16,
+            token: token.clone(),
+        });
+    }
+
+    fn include(&mut self, name: &str, token: &Token) -> Result<(), AssembleError> {
+        let path = token.path.parent().unwrap_or(Path::new("")).join(name);
+        let source = fs::read_to_string(&path)
+            .map_err(|error| token.error(format!("Could not include file ({error})")))?;
+        let canonical = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+        if let Some(start) = self.includes.iter().position(|file| *file == canonical) {
+            let cycle = self.includes[start..]
+                .iter()
+                .chain([&canonical])
+                .map(|file| file.display().to_string())
+                .collect::<Vec<_>>()
+                .join(" -> ");
+            return Err(token.error(format!("Include cycle ({cycle})")));
+        }
+        self.includes.push(canonical);
+        let result = self.assemble_tokens(tokenize(&source, &path));
+        self.includes.pop();
+        result
+    }
+
+    fn define_macro(
+        &mut self,
+        name: &str,
+        tokens: &mut impl Iterator<Item = Token>,
+        token: &Token,
+    ) -> Result<(), AssembleError> {
+        if parse_hex(name).is_some() || parse_op_code(name).is_some() {
+            return Err(token.error("Invalid macro name"));
+        }
+        if self.macros.contains_key(name) || self.label_addrs.contains_key(name) {
+            return Err(token.error("Macro is already defined"));
+        }
+        if tokens.next().is_none_or(|token| token.word != "{") {
+            return Err(token.error("Macro body must open with {"));
+        }
+        let mut body = vec![];
+        let mut comment_depth = 0;
+        for token in tokens.by_ref() {
+            if skip_comment(&token.word, &mut comment_depth) {
+                continue;
+            }
+            if token.word == "}" {
+                self.macros.insert(name.to_string(), body);
+                return Ok(());
+            }
+            body.push(token);
+        }
+        Err(token.error("Macro body is not closed"))
+    }
+
+    fn assemble_tokens(&mut self, tokens: Vec<Token>) -> Result<(), AssembleError> {
+        let mut tokens = tokens.into_iter();
+        let mut comment_depth = 0;
+        let mut comment_start = None;
+        while let Some(token) = tokens.next() {
+            let word = token.word.as_str();
+            if skip_comment(word, &mut comment_depth) {
+                if comment_start.is_none() {
+                    comment_start = Some(token.clone());
+                }
+                if comment_depth == 0 {
+                    comment_start = None;
+                }
+                continue;
+            }
+            let mut chars = word.chars();
+            let rune = chars.next().unwrap_or_default();
+            let rest = chars.as_str();
+            match rune {
+                '[' | ']' if rest.is_empty() => {}
+                // Macro
+                '%' => self.define_macro(rest, &mut tokens, &token)?,
+                // Include
+                '~' => self.include(rest, &token)?,
+                // Absolute padding
+                '|' => {
+                    self.ptr = match (parse_hex(rest), self.label_addrs.get(rest)) {
+                        (Some(addr), _) | (None, Some(&addr)) => addr as usize,
+                        (None, None) => return Err(token.error("Invalid padding")),
+                    }
+                }
+                // Relative padding
+                '$' => match parse_hex(rest) {
+                    Some(length) => self.ptr += length as usize,
+                    None => return Err(token.error("Invalid padding")),
+                },
+                // Label
+                '@' => {
+                    self.define_label(rest.to_string(), &token)?;
+                    self.scope = rest.to_string();
+                }
+                // Sublabel
+                '&' => self.define_label(self.full_name(word), &token)?,
+                // Literal hex
+                '#' => match (parse_hex(rest), rest.len()) {
+                    (Some(byte), 2) => {
+                        self.write_u8(0x80, &token)?;
+                        self.write_u8(byte as u8, &token)?;
+                    }
+                    (Some(short), 4) => {
+                        self.write_u8(0xa0, &token)?;
+                        self.write_u16(short, &token)?;
+                    }
+                    _ => return Err(token.error("Invalid hexadecimal literal")),
+                },
+                // Literal zero-page address
+                '.' => {
+                    self.write_u8(0x80, &token)?;
+                    self.add_reference(rest, rune, &token);
+                    self.write_u8(0xff, &token)?;
+                }
+                // Literal relative address
+                ',' => {
+                    self.write_u8(0x80, &token)?;
+                    self.add_reference(rest, rune, &token);
+                    self.write_u8(0xff, &token)?;
+                }
+                // Literal absolute address
+                ';' => {
+                    self.write_u8(0xa0, &token)?;
+                    self.add_reference(rest, rune, &token);
+                    self.write_u16(0xffff, &token)?;
+                }
+                // Raw zero-page address
+                '-' => {
+                    self.add_reference(rest, rune, &token);
+                    self.write_u8(0xff, &token)?;
+                }
+                // Raw relative address
+                '_' => {
+                    self.add_reference(rest, rune, &token);
+                    self.write_u8(0xff, &token)?;
+                }
+                // Raw absolute address
+                ':' => {
+                    self.add_reference(rest, rune, &token);
+                    self.write_u16(0xffff, &token)?;
+                }
+                // Raw character
+                '\'' if rest.len() == 1 => self.write_u8(rest.as_bytes()[0], &token)?,
+                // Raw word
+                '"' => {
+                    for byte in rest.bytes() {
+                        self.write_u8(byte, &token)?;
+                    }
+                }
+                _ => {
+                    if let Some(body) = self.macros.get(word) {
+                        if self.depth == MAX_MACRO_DEPTH {
+                            return Err(token.error("Macro is nested too deeply"));
+                        }
+                        let body = body.clone();
+                        self.depth += 1;
+                        self.assemble_tokens(body)?;
+                        self.depth -= 1;
+                    } else if let Some(byte) = parse_op_code(word) {
+                        self.write_u8(byte, &token)?;
+                    } else {
+                        match (parse_hex(word), word.len()) {
+                            (Some(byte), 2) => self.write_u8(byte as u8, &token)?,
+                            (Some(short), 4) => self.write_u16(short, &token)?,
+                            _ => return Err(token.error("Unknown token")),
+                        }
+                    }
+                }
+            }
+        }
+        match comment_start {
+            Some(token) => Err(token.error("Unterminated comment")),
+            None => Ok(()),
+        }
+    }
+
+    fn resolve(&mut self) -> Result<(), AssembleError> {
+        for reference in self.references.iter() {
+            let token = &reference.token;
+            let addr = match self.label_addrs.get(&reference.name) {
+                Some(addr) => *addr,
+                None => return Err(token.error("Unknown label")),
+            };
+            let index = reference.addr as usize;
+            match reference.rune {
+                '.' | '-' => {
+                    if addr > 0xff {
+                        return Err(token.error("Address is not in zero-page"));
+                    }
+                    self.data[index] = addr as u8;
+                }
+                ',' | '_' => {
+                    let delta = addr as i32 - reference.addr as i32 - 2;
+                    match i8::try_from(delta) {
+                        Ok(delta) => self.data[index] = delta as u8,
+                        Err(_) => return Err(token.error("Relative address is too far")),
+                    }
+                }
+                _ => {
+                    let [high, low] = addr.to_be_bytes();
+                    self.data[index] = high;
+                    self.data[index + 1] = low;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Assembles uxntal source, using `path` to report errors and find included files.
+pub fn assemble_source(source: &str, path: impl AsRef<Path>) -> Result<Assembly, AssembleError> {
+    let path = path.as_ref();
+    let mut assembler = Assembler::default();
+    let canonical = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    assembler.includes.push(canonical);
+    assembler.assemble_tokens(tokenize(source, path))?;
+    assembler.resolve()?;
+    Ok(Assembly {
+        rom: assembler.data[0x100..assembler.length].to_vec(),
+        labels: assembler.labels,
+    })
+}
+
+/// Assembles a uxntal file.
+pub fn assemble(path: impl AsRef<Path>) -> Result<Assembly, AssembleError> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path).map_err(|error| AssembleError {
+        path: path.to_path_buf(),
+        line: 0,
+        message: error.to_string(),
+    })?;
+    assemble_source(&source, path)
+}
@@ -0,0 +1,63 @@
+use crate::{
+    devices::Audio,
+    machine::{Machine, MachineEvent},
+};
+use hound::{SampleFormat, WavSpec, WavWriter};
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufWriter, Seek, Write},
+    path::Path,
+};
+
+/// Records the Audio devices to a 16-bit stereo WAV file a frame at a time, so the same ROM and
+/// input always produce the same samples however fast the host runs.
+pub struct AudioRecorder<W: Write + Seek = BufWriter<File>> {
+    writer: WavWriter<W>,
+    samples: Vec<i16>,
+}
+
+impl AudioRecorder {
+    /// Creates the WAV file at `path`.
+    pub fn create(path: impl AsRef<Path>) -> Result<AudioRecorder, Box<dyn Error>> {
+        AudioRecorder::new(BufWriter::new(File::create(path)?))
+    }
+}
+
+impl<W: Write + Seek> AudioRecorder<W> {
+    /// The frames in a second of emulated time, matching the screen.
+    pub const FRAME_RATE: u32 = 60;
+
+    /// Starts a stereo 16-bit WAV file in `output`.
+    pub fn new(output: W) -> Result<AudioRecorder<W>, Box<dyn Error>> {
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate: Audio::SAMPLE_RATE,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+        let frame_length = (Audio::SAMPLE_RATE / Self::FRAME_RATE * 2) as usize;
+        Ok(AudioRecorder {
+            writer: WavWriter::new(output, spec)?,
+            samples: vec![0; frame_length],
+        })
+    }
+
+    /// Renders the next frame of audio, running the audio vector of any note that finishes,
+    /// and appends it to the file.
+    pub fn record_frame(&mut self, uxn: &mut Machine) -> Result<MachineEvent, Box<dyn Error>> {
+        let event = uxn.render_audio(&mut self.samples)?;
+        let mut writer = self.writer.get_i16_writer(self.samples.len() as u32);
+        for sample in &self.samples {
+            writer.write_sample(*sample);
+        }
+        writer.flush()?;
+        Ok(event)
+    }
+
+    /// Writes the final lengths into the WAV header.
+    pub fn finish(self) -> Result<(), Box<dyn Error>> {
+        self.writer.finalize()?;
+        Ok(())
+    }
+}
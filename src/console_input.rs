@@ -0,0 +1,72 @@
+use crate::devices::ConsoleType;
+use std::{
+    io::{stdin, Read},
+    sync::mpsc::{channel, Receiver, TryRecvError},
+    thread,
+};
+
+/// Console input read from a stream on a background thread, so the host never blocks on it
+/// unless it wants to.
+pub struct ConsoleInput {
+    receiver: Receiver<u8>,
+    closed: bool,
+}
+
+impl ConsoleInput {
+    /// Reads console input from `source` until it ends or fails.
+    pub fn new(mut source: impl Read + Send + 'static) -> ConsoleInput {
+        let (sender, receiver) = channel();
+        thread::spawn(move || {
+            let mut buf = [0; 1024];
+            while let Ok(length @ 1..) = source.read(&mut buf) {
+                for byte in &buf[0..length] {
+                    if sender.send(*byte).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+        ConsoleInput {
+            receiver,
+            closed: false,
+        }
+    }
+
+    /// Reads console input from the standard input of the process.
+    pub fn stdin() -> ConsoleInput {
+        ConsoleInput::new(stdin())
+    }
+
+    /// The next byte and its console type if one is ready, without waiting. Once the stream
+    /// ends a single end of input event is returned, then nothing ever again.
+    pub fn try_next(&mut self) -> Option<(u8, ConsoleType)> {
+        match self.receiver.try_recv() {
+            Ok(byte) => Some((byte, ConsoleType::Stdin)),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => self.close(),
+        }
+    }
+
+    /// Waits for the next byte and its console type, returning `None` once the end of input
+    /// has already been delivered.
+    pub fn wait(&mut self) -> Option<(u8, ConsoleType)> {
+        match self.receiver.recv() {
+            Ok(byte) => Some((byte, ConsoleType::Stdin)),
+            Err(_) => self.close(),
+        }
+    }
+
+    /// Whether the end of input has been delivered.
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    fn close(&mut self) -> Option<(u8, ConsoleType)> {
+        if self.closed {
+            None
+        } else {
+            self.closed = true;
+            Some((0x00, ConsoleType::EndOfArguments))
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use crate::{error::StackKind, stack::Stack};
+use std::collections::HashSet;
+
+/// Why [`MachineEvent::Paused`](crate::MachineEvent::Paused) was returned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PauseReason {
+    /// The program counter reached a breakpoint, the instruction there has not run yet.
+    Breakpoint(u16),
+    /// The last instruction read a watched memory address.
+    MemoryRead(u16),
+    /// The last instruction wrote to a watched memory address.
+    MemoryWrite(u16),
+    /// The last instruction wrote to a watched device port.
+    PortWrite(u8),
+    /// A stack depth condition became true, with the depth that triggered it.
+    StackDepth(StackKind, usize),
+}
+
+/// When a stack depth condition holds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StackCondition {
+    /// More than this many bytes are on the stack.
+    Above(usize),
+    /// Fewer than this many bytes are on the stack.
+    Below(usize),
+}
+
+impl StackCondition {
+    fn holds(&self, depth: usize) -> bool {
+        match self {
+            StackCondition::Above(limit) => depth > *limit,
+            StackCondition::Below(limit) => depth < *limit,
+        }
+    }
+}
+
+/// Program counter breakpoints and stack depth conditions. Memory and device port
+/// watchpoints live on [`Memory`](crate::Memory) and [`Devices`](crate::Devices).
+#[derive(Clone, Debug, Default)]
+pub struct Breakpoints {
+    addrs: HashSet<u16>,
+    stack_conditions: Vec<(StackKind, StackCondition)>,
+}
+
+impl Breakpoints {
+    /// Pauses before the instruction at `addr` runs.
+    pub fn add(&mut self, addr: u16) {
+        self.addrs.insert(addr);
+    }
+
+    /// Removes the breakpoint at `addr`, if there is one.
+    pub fn remove(&mut self, addr: u16) {
+        self.addrs.remove(&addr);
+    }
+
+    /// Whether there is a breakpoint at `addr`.
+    pub fn contains(&self, addr: u16) -> bool {
+        self.addrs.contains(&addr)
+    }
+
+    /// The breakpoint addresses, in no particular order.
+    pub fn addrs(&self) -> impl Iterator<Item = u16> + '_ {
+        self.addrs.iter().copied()
+    }
+
+    /// Pauses when `condition` becomes true for `stack`.
+    pub fn add_stack_condition(&mut self, stack: StackKind, condition: StackCondition) {
+        self.stack_conditions.push((stack, condition));
+    }
+
+    /// The stack conditions, in the order they were added.
+    pub fn stack_conditions(&self) -> &[(StackKind, StackCondition)] {
+        &self.stack_conditions
+    }
+
+    /// Removes every breakpoint and stack condition.
+    pub fn clear(&mut self) {
+        self.addrs.clear();
+        self.stack_conditions.clear();
+    }
+
+    /// Whether there are no breakpoints and no stack conditions.
+    pub fn is_empty(&self) -> bool {
+        self.addrs.is_empty() && self.stack_conditions.is_empty()
+    }
+
+    /// Checks the stack conditions against the depths before and after an instruction, so a
+    /// condition only pauses when it starts to hold.
+    pub(crate) fn check_stacks(
+        &self,
+        depths: (usize, usize),
+        wk_stack: &Stack,
+        rt_stack: &Stack,
+    ) -> Option<PauseReason> {
+        self.stack_conditions.iter().find_map(|(stack, condition)| {
+            let (before, after) = match stack {
+                StackKind::Working => (depths.0, wk_stack.len()),
+                StackKind::Return => (depths.1, rt_stack.len()),
+            };
+            if condition.holds(after) && !condition.holds(before) {
+                Some(PauseReason::StackDepth(*stack, after))
+            } else {
+                None
+            }
+        })
+    }
+}
@@ -0,0 +1,256 @@
+use super::peek_u16;
+use crate::{
+    memory::Memory,
+    snapshot::{Decoder, Encoder, SnapshotError},
+};
+
+/// How far through a sample each note of the lowest octave moves per output sample, in 16.16
+/// fixed point.
+static ADVANCES: [u32; 12] = [
+    0x80000, 0x879c8, 0x8facd, 0x9837f, 0xa1451, 0xaadc1, 0xb504f, 0xbfc88, 0xcb2ff, 0xd7450,
+    0xe411f, 0xf1a1c,
+];
+
+pub fn vector(ports: &[u8], channel: usize) -> Option<u16> {
+    match peek_u16(ports, 0x30 + 0x10 * channel as u8) {
+        0 => None,
+        addr => Some(addr),
+    }
+}
+
+/// One of the four Audio devices, playing a copy of the sample it was started with.
+#[derive(Clone, Default)]
+struct Channel {
+    sample: Vec<u8>,
+    count: u32,
+    advance: u32,
+    period: u32,
+    age: u32,
+    attack: u32,
+    decay: u32,
+    sustain: u32,
+    release: u32,
+    position: u32,
+    volume: [u8; 2],
+    repeat: bool,
+}
+
+impl Channel {
+    /// Starts the note described by the ports, or stops the channel if the pitch is out of
+    /// range or the sample is empty.
+    fn start(&mut self, ports: &[u8], memory: &Memory) {
+        let adsr = peek_u16(ports, 0x08);
+        let addr = peek_u16(ports, 0x0c);
+        // Samples stop at the end of memory rather than wrapping around
+        let length = (peek_u16(ports, 0x0a) as u32).min(0x10000 - addr as u32) as u16;
+        let pitch = ports[0x0f] & 0x7f;
+        self.sample = memory.peek_u8s(addr, length);
+        self.volume = [ports[0x0e] >> 4, ports[0x0e] & 0x0f];
+        self.repeat = ports[0x0f] & 0x80 == 0x00;
+        if pitch >= 108 || self.sample.is_empty() {
+            self.advance = 0;
+            return;
+        }
+        self.advance = ADVANCES[pitch as usize % 12] >> (8 - pitch / 12);
+        let step = Audio::SAMPLE_RATE / 0x0f;
+        self.attack = step * (adsr >> 12) as u32;
+        self.decay = step * (adsr >> 8 & 0x0f) as u32 + self.attack;
+        self.sustain = step * (adsr >> 4 & 0x0f) as u32 + self.decay;
+        self.release = step * (adsr & 0x0f) as u32 + self.sustain;
+        self.age = 0;
+        self.position = 0;
+        let note_period = Audio::SAMPLE_RATE * 0x4000 / 11025;
+        self.period = match self.sample.len() {
+            // A single cycle of a waveform, played at the pitch
+            length @ ..=0x100 => note_period * 337 / 2 / length as u32,
+            // A recording, played faster or slower by the pitch
+            _ => note_period,
+        };
+    }
+
+    fn is_playing(&self) -> bool {
+        self.advance != 0 && self.period != 0
+    }
+
+    /// The envelope at `age` samples into the note, or `None` once it has been released.
+    fn envelope(&self, age: u32) -> Option<i32> {
+        let level = if self.release == 0 {
+            0x0888
+        } else if age < self.attack {
+            0x0888 * age / self.attack
+        } else if age < self.decay {
+            0x0444 * (2 * self.decay - self.attack - age) / (self.decay - self.attack)
+        } else if age < self.sustain {
+            0x0444
+        } else if age < self.release {
+            0x0444 * (self.release - age) / (self.release - self.sustain)
+        } else {
+            return None;
+        };
+        Some(level as i32)
+    }
+
+    /// Mixes the note into interleaved stereo `samples`, returning whether it finished.
+    fn render(&mut self, samples: &mut [i16]) -> bool {
+        if !self.is_playing() {
+            return false;
+        }
+        for frame in samples.chunks_exact_mut(2) {
+            self.count += self.advance;
+            self.position += self.count / self.period;
+            self.count %= self.period;
+            let length = self.sample.len() as u32;
+            if self.position >= length {
+                if !self.repeat {
+                    self.advance = 0;
+                    break;
+                }
+                self.position %= length;
+            }
+            let byte = self.sample[self.position as usize].wrapping_add(0x80) as i8;
+            let envelope = self.envelope(self.age).unwrap_or_else(|| {
+                self.advance = 0;
+                0
+            });
+            self.age += 1;
+            let level = byte as i32 * envelope;
+            for (output, volume) in frame.iter_mut().zip(self.volume) {
+                let mixed = *output as i32 + level * volume as i32 / 0x180;
+                *output = mixed.clamp(i16::MIN as i32, i16::MAX as i32) as i16;
+            }
+        }
+        self.advance == 0
+    }
+
+    /// The loudness of each side of the note, for `Audio/output`.
+    fn output(&self) -> u8 {
+        if !self.is_playing() {
+            return 0;
+        }
+        let level = self.envelope(self.age).unwrap_or(0);
+        let [left, right] = self.volume.map(|volume| match volume {
+            0 => 0,
+            _ => (1 + level * volume as i32 / 0x800).min(0x0f) as u8,
+        });
+        left << 4 | right
+    }
+
+    fn save(&self, encoder: &mut Encoder) {
+        encoder.bytes(&self.sample);
+        for value in [
+            self.count,
+            self.advance,
+            self.period,
+            self.age,
+            self.attack,
+            self.decay,
+            self.sustain,
+            self.release,
+            self.position,
+        ] {
+            encoder.u32(value);
+        }
+        encoder.raw(&self.volume);
+        encoder.u8(self.repeat.into());
+    }
+
+    fn restore(decoder: &mut Decoder) -> Result<Channel, SnapshotError> {
+        let channel = Channel {
+            sample: decoder.bytes()?,
+            count: decoder.u32()?,
+            advance: decoder.u32()?,
+            period: decoder.u32()?,
+            age: decoder.u32()?,
+            attack: decoder.u32()?,
+            decay: decoder.u32()?,
+            sustain: decoder.u32()?,
+            release: decoder.u32()?,
+            position: decoder.u32()?,
+            volume: decoder.array()?,
+            repeat: decoder.u8()? != 0,
+        };
+        let stages = [
+            channel.attack,
+            channel.decay,
+            channel.sustain,
+            channel.release,
+        ];
+        let in_order = stages.windows(2).all(|pair| pair[0] <= pair[1]);
+        let playable = !channel.sample.is_empty() && channel.count < channel.period;
+        match in_order && (playable || !channel.is_playing()) {
+            true => Ok(channel),
+            false => Err(SnapshotError::Corrupt),
+        }
+    }
+}
+
+/// The four Audio devices, synthesised into interleaved stereo 16-bit samples at
+/// [`Audio::SAMPLE_RATE`] whenever the host asks for more.
+#[derive(Clone, Default)]
+pub struct Audio {
+    channels: [Channel; 4],
+}
+
+impl Audio {
+    /// The samples per second, for each side, that [`Devices::render_audio`] produces.
+    ///
+    /// [`Devices::render_audio`]: super::Devices::render_audio
+    pub const SAMPLE_RATE: u32 = 44100;
+
+    /// Whether any of the four devices is playing a note.
+    pub fn is_playing(&self) -> bool {
+        self.channels.iter().any(Channel::is_playing)
+    }
+
+    /// Fills interleaved stereo `samples` with the notes being played, returning the devices
+    /// whose notes finished.
+    pub(crate) fn render(&mut self, samples: &mut [i16]) -> Vec<usize> {
+        samples.fill(0);
+        self.channels
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(channel, state)| state.render(samples).then_some(channel))
+            .collect()
+    }
+
+    /// Reads `Audio/position` and `Audio/output`, which change as the note plays.
+    pub(crate) fn device_input_u8(&self, channel: usize, port: u8, ports: &[u8]) -> u8 {
+        let state = &self.channels[channel];
+        match port {
+            0x02 => (state.position as u16).to_be_bytes()[0],
+            0x03 => (state.position as u16).to_be_bytes()[1],
+            0x04 => state.output(),
+            _ => ports[port as usize],
+        }
+    }
+
+    pub(crate) fn trigger_event(
+        &mut self,
+        channel: usize,
+        port: u8,
+        ports: &[u8],
+        memory: &Memory,
+    ) {
+        // Pitch
+        if port == 0x0f {
+            self.channels[channel].start(ports, memory);
+        }
+    }
+
+    pub(crate) fn save(&self, encoder: &mut Encoder) {
+        for channel in &self.channels {
+            channel.save(encoder);
+        }
+    }
+
+    pub(crate) fn restore(decoder: &mut Decoder) -> Result<Audio, SnapshotError> {
+        Ok(Audio {
+            channels: [
+                Channel::restore(decoder)?,
+                Channel::restore(decoder)?,
+                Channel::restore(decoder)?,
+                Channel::restore(decoder)?,
+            ],
+        })
+    }
+}
@@ -1,4 +1,30 @@
 use super::peek_u16;
+use std::io::Write;
+
+/// What the byte in `Console/read` is, as reported by `Console/type`. `EndOfArguments` also
+/// marks the end of the input stream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConsoleType {
+    /// A byte read from standard input.
+    Stdin,
+    /// A byte of a command line argument.
+    Argument,
+    /// The space between two arguments.
+    ArgumentSpacer,
+    /// The last argument has been sent.
+    EndOfArguments,
+}
+
+impl From<ConsoleType> for u8 {
+    fn from(console_type: ConsoleType) -> Self {
+        match console_type {
+            ConsoleType::Stdin => 0x01,
+            ConsoleType::Argument => 0x02,
+            ConsoleType::ArgumentSpacer => 0x03,
+            ConsoleType::EndOfArguments => 0x04,
+        }
+    }
+}
 
 pub fn vector(ports: &[u8]) -> Option<u16> {
     match peek_u16(ports, 0x10) {
@@ -7,17 +33,22 @@ pub fn vector(ports: &[u8]) -> Option<u16> {
     }
 }
 
-pub fn trigger_event(port: u8, ports: &[u8]) {
+pub fn input(ports: &mut [u8], byte: u8, console_type: ConsoleType) {
+    // Read
+    ports[0x02] = byte;
+    // Type
+    ports[0x07] = console_type.into();
+}
+
+pub fn trigger_event(port: u8, ports: &[u8], output: &mut dyn Write, error: &mut dyn Write) {
     match port {
         // Write
         0x08 => {
-            let ch = ports[port as usize] as char;
-            print!("{ch}");
+            output.write_all(&ports[port as usize..=port as usize]).ok();
         }
         // Error
         0x09 => {
-            let ch = ports[port as usize] as char;
-            eprint!("{ch}");
+            error.write_all(&ports[port as usize..=port as usize]).ok();
         }
         _ => {}
     }
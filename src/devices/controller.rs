@@ -0,0 +1,58 @@
+use super::peek_u16;
+
+/// A button on the Controller device, as a bit of `Controller/button`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Button {
+    /// Bit 0.
+    A,
+    /// Bit 1.
+    B,
+    /// Bit 2.
+    Select,
+    /// Bit 3.
+    Start,
+    /// Bit 4.
+    Up,
+    /// Bit 5.
+    Down,
+    /// Bit 6.
+    Left,
+    /// Bit 7.
+    Right,
+}
+
+impl From<Button> for u8 {
+    fn from(button: Button) -> Self {
+        match button {
+            Button::A => 0x01,
+            Button::B => 0x02,
+            Button::Select => 0x04,
+            Button::Start => 0x08,
+            Button::Up => 0x10,
+            Button::Down => 0x20,
+            Button::Left => 0x40,
+            Button::Right => 0x80,
+        }
+    }
+}
+
+pub fn vector(ports: &[u8]) -> Option<u16> {
+    match peek_u16(ports, 0x80) {
+        0 => None,
+        addr => Some(addr),
+    }
+}
+
+/// Sets or clears `button` in `Controller/button`, returning whether it changed.
+pub fn set_button(ports: &mut [u8], button: Button, pressed: bool) -> bool {
+    let buttons = ports[0x02];
+    ports[0x02] = match pressed {
+        true => buttons | u8::from(button),
+        false => buttons & !u8::from(button),
+    };
+    ports[0x02] != buttons
+}
+
+pub fn set_key(ports: &mut [u8], key: u8) {
+    ports[0x03] = key;
+}
@@ -1,28 +1,68 @@
+use crate::snapshot::{Decoder, Encoder, SnapshotError};
 use std::{
     error::Error,
     time::{Instant, SystemTime, UNIX_EPOCH},
 };
 use tz::{error::DateTimeError, DateTime, TimeZone, TzError, UtcDateTime};
 
+/// The clock behind the DateTime device.
 pub enum DeviceSystemTime {
+    /// The host clock, in the local time zone.
     Local,
+    /// The host clock, in UTC.
     Utc,
+    /// A clock that started at `date_time` and runs with the host.
     Custom {
+        /// The time when the clock was set.
         date_time: DeviceDateTime,
+        /// When, on the host, the clock was set.
         from: Instant,
     },
+    /// A clock that never moves.
     Static(DeviceDateTime),
 }
 
 impl DeviceSystemTime {
+    /// A clock that starts at `date_time` now.
     pub fn new(date_time: DeviceDateTime) -> DeviceSystemTime {
         DeviceSystemTime::Custom {
             date_time,
             from: Instant::now(),
         }
     }
+
+    /// Saves the time mode, a running clock is saved as the time it shows now.
+    pub(crate) fn save(&self, encoder: &mut Encoder) {
+        match self {
+            DeviceSystemTime::Local => encoder.u8(0),
+            DeviceSystemTime::Utc => encoder.u8(1),
+            DeviceSystemTime::Custom { date_time, from } => {
+                let date_time = date_time.from_then(from).unwrap_or_default();
+                encoder.u8(2);
+                encoder.i64(date_time.unix_time().unwrap_or_default());
+            }
+            DeviceSystemTime::Static(date_time) => {
+                encoder.u8(3);
+                encoder.i64(date_time.unix_time().unwrap_or_default());
+            }
+        }
+    }
+
+    pub(crate) fn restore(decoder: &mut Decoder) -> Result<DeviceSystemTime, SnapshotError> {
+        let date_time = |decoder: &mut Decoder| {
+            DeviceDateTime::new(decoder.i64()?).map_err(|_| SnapshotError::Corrupt)
+        };
+        match decoder.u8()? {
+            0 => Ok(DeviceSystemTime::Local),
+            1 => Ok(DeviceSystemTime::Utc),
+            2 => Ok(DeviceSystemTime::new(date_time(decoder)?)),
+            3 => Ok(DeviceSystemTime::Static(date_time(decoder)?)),
+            _ => Err(SnapshotError::Corrupt),
+        }
+    }
 }
 
+/// A date and time broken down the way the DateTime device reports it.
 #[derive(Clone)]
 pub struct DeviceDateTime {
     year: u16,
@@ -42,6 +82,7 @@ impl Default for DeviceDateTime {
 }
 
 impl DeviceDateTime {
+    /// Breaks down seconds since the unix epoch, in UTC.
     pub fn new(unix_time: i64) -> Result<DeviceDateTime, Box<dyn Error>> {
         let date_time = UtcDateTime::from_timespec(unix_time, 0)?;
         Ok(DeviceDateTime {
@@ -56,16 +97,19 @@ impl DeviceDateTime {
         })
     }
 
+    /// Midnight, January 1st 1970, UTC.
     pub fn unix_epoch() -> DeviceDateTime {
         DeviceDateTime::new(0).expect("valid unix time")
     }
 
+    /// The current time in UTC.
     pub fn utc() -> Result<DeviceDateTime, Box<dyn Error>> {
         let now = SystemTime::now();
         let unix_time = now.duration_since(UNIX_EPOCH)?.as_secs();
         DeviceDateTime::new(unix_time as i64)
     }
 
+    /// The current time in the local time zone.
     pub fn local() -> Result<DeviceDateTime, Box<dyn Error>> {
         let time = TimeZone::local()?;
         let local_time_type = time.find_current_local_time_type()?;
@@ -75,6 +119,7 @@ impl DeviceDateTime {
         DeviceDateTime::new(date_time.unix_time())
     }
 
+    /// The seconds since the Unix epoch.
     pub fn unix_time(&self) -> Result<i64, DateTimeError> {
         let time = UtcDateTime::new(
             self.year as i32,
@@ -131,13 +176,7 @@ pub fn device_input_u8(port: u8, date_time_type: &DeviceSystemTime) -> u8 {
         0x09 => (date_time.day_of_the_year & 0xff) as u8,
         // Is daylight savings
         0x0a => match date_time_type {
-            DeviceSystemTime::Local => {
-                if DeviceDateTime::is_dst().unwrap_or(false) {
-                    1
-                } else {
-                    0
-                }
-            }
+            DeviceSystemTime::Local => DeviceDateTime::is_dst().unwrap_or(false).into(),
             _ => 0,
         },
         _ => 0,
@@ -80,7 +80,7 @@ impl PhysicalFileSystem {
 
     pub fn write(&mut self, path: &str, buf: &[u8]) -> Result<usize, Box<dyn Error>> {
         let path = safety_check(&self.cwd, path, self.safety)?;
-        let mut file = OpenOptions::new().write(true).append(true).open(path)?;
+        let mut file = OpenOptions::new().append(true).open(path)?;
         file.write_all(buf)?;
         Ok(buf.len())
     }
@@ -2,7 +2,10 @@ mod file_interface;
 mod file_system;
 mod virtual_file_system;
 use super::{peek_u16, poke_u16};
-use crate::memory::Memory;
+use crate::{
+    memory::Memory,
+    snapshot::{Decoder, Encoder, SnapshotError},
+};
 pub use file_interface::FileInterface;
 pub use file_system::PhysicalFileSystem;
 use std::collections::VecDeque;
@@ -47,6 +50,52 @@ impl FileDevice {
             ..Default::default()
         }
     }
+
+    pub fn interface(&self) -> &FileInterface {
+        &self.interface
+    }
+
+    /// Saves the read or write in progress, but not the interface, which belongs to the host.
+    pub fn save(&self, encoder: &mut Encoder) {
+        match &self.state {
+            None => encoder.u8(0),
+            Some(State::Read(ReadType::File(bytes))) => {
+                encoder.u8(1);
+                encoder.bytes(bytes);
+            }
+            Some(State::Read(ReadType::Stats(entries))) => {
+                encoder.u8(2);
+                encoder.u32(entries.len() as u32);
+                for entry in entries {
+                    encoder.bytes(entry);
+                }
+            }
+            Some(State::Write(path)) => {
+                encoder.u8(3);
+                encoder.string(path);
+            }
+        }
+    }
+
+    pub fn restore(
+        interface: FileInterface,
+        decoder: &mut Decoder,
+    ) -> Result<FileDevice, SnapshotError> {
+        let state = match decoder.u8()? {
+            0 => None,
+            1 => Some(State::Read(ReadType::File(decoder.bytes()?))),
+            2 => {
+                let mut entries = VecDeque::new();
+                for _ in 0..decoder.u32()? {
+                    entries.push_back(decoder.bytes()?);
+                }
+                Some(State::Read(ReadType::Stats(entries)))
+            }
+            3 => Some(State::Write(decoder.string()?)),
+            _ => return Err(SnapshotError::Corrupt),
+        };
+        Ok(FileDevice { interface, state })
+    }
 }
 
 impl FileDevice {
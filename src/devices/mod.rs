@@ -1,11 +1,30 @@
+mod audio;
 mod console;
+mod controller;
 mod date_time;
 mod file_device;
+mod mouse;
+mod screen;
 mod system;
-use crate::{memory::Memory, stack::Stack};
-use date_time::{DeviceDateTime, DeviceSystemTime};
+use crate::{
+    debugger::PauseReason,
+    memory::Memory,
+    snapshot::{Decoder, Encoder, SnapshotError},
+    stack::Stack,
+};
+pub use audio::Audio;
+pub use console::ConsoleType;
+pub use controller::Button;
+pub use date_time::{DeviceDateTime, DeviceSystemTime};
 use file_device::{FileDevice, FileInterface, PhysicalFileSystem, VirtualFileSystem};
-use std::path::Path;
+pub use mouse::MouseButton;
+pub use screen::Screen;
+use std::{
+    collections::HashSet,
+    error::Error,
+    io::{stderr, stdout, Write},
+    path::Path,
+};
 
 fn peek_u16(ports: &[u8], addr: u8) -> u16 {
     let low = ports[addr as usize];
@@ -20,48 +39,276 @@ fn poke_u16(ports: &mut [u8], addr: u8, short: u16) {
     ports[addr_2 as usize] = short_bytes[1];
 }
 
+/// The device page and the host state behind each Varvara device.
 pub struct Devices {
     system_time: DeviceSystemTime,
     ports: [u8; 256],
     file_0: FileDevice,
     file_1: FileDevice,
+    screen: Screen,
+    audio: Audio,
+    console_output: Box<dyn Write>,
+    console_error: Box<dyn Write>,
+    port_watches: HashSet<u8>,
+    watch_hit: Option<PauseReason>,
+    log_writes: bool,
+    ports_before: Option<[u8; 256]>,
 }
 
 impl Default for Devices {
     fn default() -> Devices {
         let date_time_type = DeviceSystemTime::Local;
-        let ports = [0; 256];
+        let mut ports = [0; 256];
         let file_0 = FileDevice::default();
         let file_1 = FileDevice::default();
+        let screen = Screen::default();
+        screen.write_size(&mut ports[0x20..=0x2f]);
         Devices {
             system_time: date_time_type,
             ports,
             file_0,
             file_1,
+            screen,
+            audio: Audio::default(),
+            console_output: Box::new(stdout()),
+            console_error: Box::new(stderr()),
+            port_watches: HashSet::new(),
+            watch_hit: None,
+            log_writes: false,
+            ports_before: None,
         }
     }
 }
 
 impl Devices {
+    /// Reads a byte from a device port, as `DEI` does.
     pub fn device_input_u8(&self, port: u8) -> u8 {
         match port {
+            0x30..=0x6f => {
+                let channel = (port - 0x30) as usize / 0x10;
+                let base = 0x30 + channel * 0x10;
+                let ports = &self.ports[base..base + 0x10];
+                self.audio.device_input_u8(channel, port & 0x0f, ports)
+            }
             0xc0..=0xcf => date_time::device_input_u8(port - 0xc0, &self.system_time),
             _ => self.ports[port as usize],
         }
     }
 
+    /// Reads a short from two device ports, as `DEI2` does.
     pub fn device_input_u16(&mut self, port: u8) -> u16 {
         let low = self.device_input_u8(port);
         let high = self.device_input_u8(port.wrapping_add(1));
         u16::from_be_bytes([low, high])
     }
 
+    /// The `System/state` the ROM halted with, if it has.
+    pub fn halt_state(&self) -> Option<u8> {
+        system::state(&self.ports)
+    }
+
+    /// The `System/vector` address, if the ROM has set an error handler.
+    pub fn system_vector(&self) -> Option<u16> {
+        system::vector(&self.ports)
+    }
+
+    /// The `Console/vector` address, if the ROM has set one.
     pub fn console_vector(&self) -> Option<u16> {
         console::vector(&self.ports)
     }
 
-    pub fn console_event_value(&mut self, byte: u8) {
-        self.ports[0x12] = byte;
+    /// The `Controller/vector` address, if the ROM has set one.
+    pub fn controller_vector(&self) -> Option<u16> {
+        controller::vector(&self.ports)
+    }
+
+    /// Presses or releases `button`, returning whether `Controller/button` changed.
+    pub fn set_button(&mut self, button: Button, pressed: bool) -> bool {
+        controller::set_button(&mut self.ports[0x80..=0x8f], button, pressed)
+    }
+
+    /// Sets the byte returned by `Controller/key`.
+    pub fn set_key(&mut self, key: u8) {
+        controller::set_key(&mut self.ports[0x80..=0x8f], key);
+    }
+
+    /// The `Mouse/vector` address, if the ROM has set one.
+    pub fn mouse_vector(&self) -> Option<u16> {
+        mouse::vector(&self.ports)
+    }
+
+    /// Moves the pointer to `x`, `y` in screen pixels, returning whether it moved.
+    pub fn set_mouse_position(&mut self, x: u16, y: u16) -> bool {
+        mouse::set_position(&mut self.ports[0x90..=0x9f], x, y)
+    }
+
+    /// Presses or releases a mouse button, returning whether `Mouse/state` changed.
+    pub fn set_mouse_button(&mut self, button: MouseButton, pressed: bool) -> bool {
+        mouse::set_button(&mut self.ports[0x90..=0x9f], button, pressed)
+    }
+
+    /// Sets the scroll reported by the next mouse vector, positive values scrolling right and
+    /// down.
+    pub fn set_mouse_scroll(&mut self, x: i16, y: i16) {
+        mouse::set_scroll(&mut self.ports[0x90..=0x9f], x, y);
+    }
+
+    /// The `Screen/vector` address, if the ROM has set one. The host runs it once per frame.
+    pub fn screen_vector(&self) -> Option<u16> {
+        screen::vector(&self.ports)
+    }
+
+    /// The four System colours as 8-bit RGB, decoded from `System/red`, `System/green` and
+    /// `System/blue`.
+    pub fn palette(&self) -> [[u8; 3]; 4] {
+        system::palette(&self.ports)
+    }
+
+    /// The screen as RGB bytes in the System colours, a row at a time.
+    pub fn screen_rgb(&self) -> Vec<u8> {
+        self.screen.rgb(&self.palette())
+    }
+
+    /// Saves the screen in the System colours, as a PPM image if the path ends in `.ppm` or as
+    /// a PNG image otherwise.
+    pub fn save_screen(&self, path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+        self.screen.save_image(&self.palette(), path)
+    }
+
+    /// The Screen device framebuffer.
+    pub fn screen(&self) -> &Screen {
+        &self.screen
+    }
+
+    /// Resizes the screen as if the ROM had written `Screen/width` and `Screen/height`.
+    pub fn resize_screen(&mut self, width: u16, height: u16) {
+        self.screen
+            .resize(width, height, &mut self.ports[0x20..=0x2f]);
+    }
+
+    /// The `Audio/vector` address of one of the four Audio devices, if the ROM has set one. The
+    /// host runs it when the device's note finishes.
+    pub fn audio_vector(&self, channel: usize) -> Option<u16> {
+        audio::vector(&self.ports, channel)
+    }
+
+    /// The Audio devices.
+    pub fn audio(&self) -> &Audio {
+        &self.audio
+    }
+
+    /// Fills interleaved stereo `samples` at [`Audio::SAMPLE_RATE`] with the notes being played,
+    /// returning the Audio devices whose notes finished.
+    pub fn render_audio(&mut self, samples: &mut [i16]) -> Vec<usize> {
+        self.audio.render(samples)
+    }
+
+    /// Sets the byte returned by `Console/read` and its `Console/type`.
+    pub fn console_input(&mut self, byte: u8, console_type: ConsoleType) {
+        console::input(&mut self.ports[0x10..=0x1f], byte, console_type);
+    }
+
+    /// Sets `Console/type` to the number of arguments before the reset vector runs, as the
+    /// reference does, so the ROM can tell whether arguments will follow. Counts past 0xff
+    /// are reported as 0xff rather than wrapping round to look like none.
+    pub fn console_argument_count(&mut self, count: usize) {
+        self.ports[0x17] = count.min(0xff) as u8;
+    }
+
+    /// Sends bytes written to `Console/write` to `output` instead of the standard output.
+    pub fn set_console_output(&mut self, output: impl Write + 'static) {
+        self.console_output = Box::new(output);
+    }
+
+    /// Sends bytes written to `Console/error`, and the stacks printed by `System/debug`, to
+    /// `error` instead of the standard error.
+    pub fn set_console_error(&mut self, error: impl Write + 'static) {
+        self.console_error = Box::new(error);
+    }
+
+    /// Flushes both console sinks.
+    pub fn flush(&mut self) {
+        self.console_output.flush().ok();
+        self.console_error.flush().ok();
+    }
+
+    /// Pauses the machine after an instruction writes to `port`.
+    pub fn watch_port(&mut self, port: u8) {
+        self.port_watches.insert(port);
+    }
+
+    /// Removes the watch on `port`.
+    pub fn unwatch_port(&mut self, port: u8) {
+        self.port_watches.remove(&port);
+    }
+
+    pub(crate) fn take_watch_hit(&mut self) -> Option<PauseReason> {
+        self.watch_hit.take()
+    }
+
+    fn check_write(&mut self, port: u8) {
+        if !self.port_watches.is_empty() && self.port_watches.contains(&port) {
+            self.watch_hit = Some(PauseReason::PortWrite(port));
+        }
+    }
+
+    /// Starts or stops logging the old value of every port written. The ports are only copied
+    /// when an instruction first writes to one, as most never do.
+    pub(crate) fn log_writes(&mut self, log: bool) {
+        self.log_writes = log;
+        self.ports_before = None;
+    }
+
+    pub(crate) fn take_write_log(&mut self) -> Vec<(u8, u8)> {
+        let Some(before) = self.ports_before.take() else {
+            return vec![];
+        };
+        (0..=255u8)
+            .filter(|port| before[*port as usize] != self.ports[*port as usize])
+            .map(|port| (port, before[port as usize]))
+            .collect()
+    }
+
+    #[inline]
+    fn log_write(&mut self) {
+        if self.log_writes && self.ports_before.is_none() {
+            self.ports_before = Some(self.ports);
+        }
+    }
+
+    /// Puts back a port overwritten by an instruction, without triggering a device event.
+    pub(crate) fn undo_write(&mut self, port: u8, byte: u8) {
+        self.ports[port as usize] = byte;
+    }
+
+    /// Saves the ports, the time mode, any file reads or writes in progress, the screen and the
+    /// notes being played. The file system is left out, so restoring keeps the one the host
+    /// chose.
+    pub(crate) fn save(&self, encoder: &mut Encoder) {
+        encoder.raw(&self.ports);
+        self.system_time.save(encoder);
+        self.file_0.save(encoder);
+        self.file_1.save(encoder);
+        self.screen.save(encoder);
+        self.audio.save(encoder);
+    }
+
+    /// Restores what [`Devices::save`] saved, leaving the devices untouched on an error.
+    pub(crate) fn restore(&mut self, decoder: &mut Decoder) -> Result<(), SnapshotError> {
+        let ports = decoder.array()?;
+        let system_time = DeviceSystemTime::restore(decoder)?;
+        let interface = self.file_0.interface().clone();
+        let file_0 = FileDevice::restore(interface.clone(), decoder)?;
+        let file_1 = FileDevice::restore(interface, decoder)?;
+        let screen = Screen::restore(decoder)?;
+        let audio = Audio::restore(decoder)?;
+        self.ports = ports;
+        self.system_time = system_time;
+        self.file_0 = file_0;
+        self.file_1 = file_1;
+        self.screen = screen;
+        self.audio = audio;
+        Ok(())
     }
 
     fn use_interface(&mut self, interface: FileInterface) {
@@ -69,30 +316,36 @@ impl Devices {
         self.file_1 = FileDevice::with_interface(interface);
     }
 
+    /// Backs the File devices with the host file system rooted at `path`, refusing `..` when `safety` is set.
     pub fn use_phycial_file_system(&mut self, path: impl AsRef<Path>, safety: bool) {
         self.use_interface(FileInterface::FileSystem(PhysicalFileSystem::new(
             path, safety,
         )));
     }
 
+    /// Backs the File devices with an in-memory file system.
     pub fn use_virtual_file_system(&mut self) {
         self.use_interface(FileInterface::VirtualFileSystem(
             VirtualFileSystem::default(),
         ));
     }
 
+    /// Reports the local time of the host on the DateTime device.
     pub fn use_local_time(&mut self) {
         self.system_time = DeviceSystemTime::Local;
     }
 
+    /// Reports UTC on the DateTime device.
     pub fn use_utc(&mut self) {
         self.system_time = DeviceSystemTime::Utc;
     }
 
+    /// Reports a clock that starts at `date_time` and keeps running.
     pub fn set_time(&mut self, date_time: DeviceDateTime) {
         self.system_time = DeviceSystemTime::new(date_time);
     }
 
+    /// Reports `date_time` without ever advancing.
     pub fn use_static_time(&mut self, date_time: DeviceDateTime) {
         self.system_time = DeviceSystemTime::Static(date_time);
     }
@@ -107,16 +360,39 @@ impl Devices {
         match port {
             // System
             0x00..=0x0f => {
-                return system::trigger_event(port, &self.ports[0x00..=0x0f], wk_stack, rt_stack)
+                return system::trigger_event(
+                    port,
+                    &self.ports[0x00..=0x0f],
+                    wk_stack,
+                    rt_stack,
+                    &mut self.console_error,
+                );
             }
             // Console
             0x10..=0x1f => {
-                console::trigger_event(port - 0x10, &self.ports[0x10..=0x1f]);
+                console::trigger_event(
+                    port - 0x10,
+                    &self.ports[0x10..=0x1f],
+                    &mut self.console_output,
+                    &mut self.console_error,
+                );
             }
             // Screen
-            0x20..=0x2f => {}
+            0x20..=0x2f => {
+                self.screen
+                    .trigger_event(port - 0x20, &mut self.ports[0x20..=0x2f], memory);
+            }
             // Audio
-            0x30..=0x6f => {}
+            0x30..=0x6f => {
+                let channel = (port - 0x30) as usize / 0x10;
+                let base = 0x30 + channel * 0x10;
+                self.audio.trigger_event(
+                    channel,
+                    port & 0x0f,
+                    &self.ports[base..base + 0x10],
+                    memory,
+                );
+            }
             // Midi
             0x70..=0x7f => {}
             // Controller
@@ -143,6 +419,7 @@ impl Devices {
         None
     }
 
+    /// Writes a short to two device ports, as `DEO2` does, returning the halt state if the write halts the machine.
     pub fn device_output_u16(
         &mut self,
         port: u8,
@@ -151,10 +428,13 @@ impl Devices {
         rt_stack: &Stack,
         memory: &mut Memory,
     ) -> Option<u8> {
+        self.log_write();
         let short_bytes = short.to_be_bytes();
         self.ports[port as usize] = short_bytes[0];
         let addr_2 = port.wrapping_add(1);
         self.ports[addr_2 as usize] = short_bytes[1];
+        self.check_write(port);
+        self.check_write(addr_2);
         if let Some(state) = self.trigger_event(port, wk_stack, rt_stack, memory) {
             Some(state)
         } else {
@@ -162,6 +442,7 @@ impl Devices {
         }
     }
 
+    /// Writes a byte to a device port, as `DEO` does, returning the halt state if the write halts the machine.
     pub fn device_output_u8(
         &mut self,
         port: u8,
@@ -170,7 +451,9 @@ impl Devices {
         rt_stack: &Stack,
         memory: &mut Memory,
     ) -> Option<u8> {
+        self.log_write();
         self.ports[port as usize] = byte;
+        self.check_write(port);
         self.trigger_event(port, wk_stack, rt_stack, memory)
     }
 }
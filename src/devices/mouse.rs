@@ -0,0 +1,53 @@
+use super::{peek_u16, poke_u16};
+
+/// A button on the Mouse device, as a bit of `Mouse/state`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MouseButton {
+    /// Bit 0.
+    Left,
+    /// Bit 1.
+    Middle,
+    /// Bit 2.
+    Right,
+}
+
+impl From<MouseButton> for u8 {
+    fn from(button: MouseButton) -> Self {
+        match button {
+            MouseButton::Left => 0x01,
+            MouseButton::Middle => 0x02,
+            MouseButton::Right => 0x04,
+        }
+    }
+}
+
+pub fn vector(ports: &[u8]) -> Option<u16> {
+    match peek_u16(ports, 0x90) {
+        0 => None,
+        addr => Some(addr),
+    }
+}
+
+/// Moves the pointer, returning whether `Mouse/x` or `Mouse/y` changed.
+pub fn set_position(ports: &mut [u8], x: u16, y: u16) -> bool {
+    let changed = peek_u16(ports, 0x02) != x || peek_u16(ports, 0x04) != y;
+    poke_u16(ports, 0x02, x);
+    poke_u16(ports, 0x04, y);
+    changed
+}
+
+/// Sets or clears `button` in `Mouse/state`, returning whether it changed.
+pub fn set_button(ports: &mut [u8], button: MouseButton, pressed: bool) -> bool {
+    let state = ports[0x06];
+    ports[0x06] = match pressed {
+        true => state | u8::from(button),
+        false => state & !u8::from(button),
+    };
+    ports[0x06] != state
+}
+
+/// Sets `Mouse/scrollx` and `Mouse/scrolly`, positive values scrolling right and down.
+pub fn set_scroll(ports: &mut [u8], x: i16, y: i16) {
+    poke_u16(ports, 0x0a, x as u16);
+    poke_u16(ports, 0x0c, y as u16);
+}
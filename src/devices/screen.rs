@@ -0,0 +1,318 @@
+use super::{peek_u16, poke_u16};
+use crate::{
+    memory::Memory,
+    snapshot::{Decoder, Encoder, SnapshotError},
+};
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+/// The colour a sprite pixel is drawn with for each blend mode, by the colour of the pixel.
+static BLENDING: [[u8; 16]; 4] = [
+    [0, 0, 0, 0, 1, 0, 1, 1, 2, 2, 0, 2, 3, 3, 3, 0],
+    [0, 1, 2, 3, 0, 1, 2, 3, 0, 1, 2, 3, 0, 1, 2, 3],
+    [1, 2, 3, 1, 1, 2, 3, 1, 1, 2, 3, 1, 1, 2, 3, 1],
+    [2, 3, 1, 2, 2, 3, 1, 2, 2, 3, 1, 2, 2, 3, 1, 2],
+];
+
+pub fn vector(ports: &[u8]) -> Option<u16> {
+    match peek_u16(ports, 0x20) {
+        0 => None,
+        addr => Some(addr),
+    }
+}
+
+/// The background and foreground layers of the Screen device, each pixel one of the four
+/// System colours.
+#[derive(Clone)]
+pub struct Screen {
+    width: u16,
+    height: u16,
+    background: Vec<u8>,
+    foreground: Vec<u8>,
+}
+
+impl Default for Screen {
+    fn default() -> Screen {
+        Screen::new(0x200, 0x140)
+    }
+}
+
+impl Screen {
+    /// The smallest width or height the screen can have.
+    const MIN_SIZE: u16 = 0x0008;
+    /// The first width or height too large for the screen.
+    const MAX_SIZE: u16 = 0x0800;
+
+    fn new(width: u16, height: u16) -> Screen {
+        let size = width as usize * height as usize;
+        Screen {
+            width,
+            height,
+            background: vec![0; size],
+            foreground: vec![0; size],
+        }
+    }
+
+    /// The width in pixels.
+    pub fn width(&self) -> u16 {
+        self.width
+    }
+
+    /// The height in pixels.
+    pub fn height(&self) -> u16 {
+        self.height
+    }
+
+    /// The background layer, a row at a time.
+    pub fn background(&self) -> &[u8] {
+        &self.background
+    }
+
+    /// The foreground layer, a row at a time.
+    pub fn foreground(&self) -> &[u8] {
+        &self.foreground
+    }
+
+    /// The colour shown at `x`, `y`, the foreground unless it is colour 0 there.
+    pub fn pixel(&self, x: u16, y: u16) -> Option<u8> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let index = x as usize + y as usize * self.width as usize;
+        match self.foreground[index] {
+            0 => Some(self.background[index]),
+            color => Some(color),
+        }
+    }
+
+    /// The colour shown at every pixel, a row at a time.
+    pub fn pixels(&self) -> Vec<u8> {
+        self.foreground
+            .iter()
+            .zip(self.background.iter())
+            .map(|(foreground, background)| match foreground {
+                0 => *background,
+                _ => *foreground,
+            })
+            .collect()
+    }
+
+    /// The colour shown at every pixel as RGB bytes, a row at a time.
+    pub fn rgb(&self, palette: &[[u8; 3]; 4]) -> Vec<u8> {
+        self.pixels()
+            .into_iter()
+            .flat_map(|color| palette[color as usize])
+            .collect()
+    }
+
+    /// Writes the screen as a binary PPM image.
+    pub fn write_ppm(
+        &self,
+        palette: &[[u8; 3]; 4],
+        output: &mut dyn Write,
+    ) -> Result<(), Box<dyn Error>> {
+        write!(output, "P6\n{} {}\n255\n", self.width, self.height)?;
+        output.write_all(&self.rgb(palette))?;
+        Ok(())
+    }
+
+    /// Writes the screen as an RGB PNG image.
+    pub fn write_png(
+        &self,
+        palette: &[[u8; 3]; 4],
+        output: &mut dyn Write,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut encoder = png::Encoder::new(output, self.width.into(), self.height.into());
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.rgb(palette))?;
+        writer.finish()?;
+        Ok(())
+    }
+
+    /// Saves the screen as a PPM image if the path ends in `.ppm`, or as a PNG image otherwise.
+    pub fn save_image(
+        &self,
+        palette: &[[u8; 3]; 4],
+        path: impl AsRef<Path>,
+    ) -> Result<(), Box<dyn Error>> {
+        let path = path.as_ref();
+        let mut output = BufWriter::new(File::create(path)?);
+        match path.extension() {
+            Some(extension) if extension.eq_ignore_ascii_case("ppm") => {
+                self.write_ppm(palette, &mut output)?
+            }
+            _ => self.write_png(palette, &mut output)?,
+        }
+        output.flush()?;
+        Ok(())
+    }
+
+    /// Clears both layers and changes the size, ignoring sizes the screen cannot be. The
+    /// size is written back to the ports either way.
+    pub(crate) fn resize(&mut self, width: u16, height: u16, ports: &mut [u8]) {
+        let range = Screen::MIN_SIZE..Screen::MAX_SIZE;
+        if range.contains(&width) && range.contains(&height) {
+            *self = Screen::new(width, height);
+        }
+        self.write_size(ports);
+    }
+
+    pub(crate) fn write_size(&self, ports: &mut [u8]) {
+        poke_u16(ports, 0x02, self.width);
+        poke_u16(ports, 0x04, self.height);
+    }
+
+    fn layer(&mut self, control: u8) -> &mut [u8] {
+        if control & 0x40 != 0x00 {
+            &mut self.foreground
+        } else {
+            &mut self.background
+        }
+    }
+
+    fn put(&mut self, control: u8, x: u16, y: u16, color: u8) {
+        if x < self.width && y < self.height {
+            let index = x as usize + y as usize * self.width as usize;
+            self.layer(control)[index] = color;
+        }
+    }
+
+    fn fill(&mut self, control: u8, x: u16, y: u16, color: u8) {
+        let (from_x, to_x) = match control & 0x10 {
+            0x00 => (x, self.width),
+            _ => (0, x.min(self.width)),
+        };
+        let (from_y, to_y) = match control & 0x20 {
+            0x00 => (y, self.height),
+            _ => (0, y.min(self.height)),
+        };
+        let width = self.width as usize;
+        let layer = self.layer(control);
+        for y in from_y..to_y {
+            let row = y as usize * width;
+            for x in from_x..to_x {
+                layer[row + x as usize] = color;
+            }
+        }
+    }
+
+    fn blit(&mut self, control: u8, addr: u16, x: u16, y: u16, memory: &Memory) {
+        let two_bpp = control & 0x80 != 0x00;
+        let flip_x = control & 0x10 != 0x00;
+        let flip_y = control & 0x20 != 0x00;
+        let blend = (control & 0x0f) as usize;
+        // Colour 0 is transparent in the blend modes that would draw it unchanged
+        let opaque = !matches!(blend, 0x05 | 0x0a | 0x0f);
+        let bytes = memory.peek_u8s(addr, if two_bpp { 16 } else { 8 });
+        for v in 0..8 {
+            let low = bytes[v];
+            let high = if two_bpp { bytes[v + 8] } else { 0 };
+            let row_y = y.wrapping_add(if flip_y { 7 - v as u16 } else { v as u16 });
+            for h in 0..8 {
+                let bit = 7 - h;
+                let color = ((low >> bit) & 1) | (((high >> bit) & 1) << 1);
+                if opaque || color != 0 {
+                    let column_x = x.wrapping_add(if flip_x { 7 - h as u16 } else { h as u16 });
+                    self.put(control, column_x, row_y, BLENDING[color as usize][blend]);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn trigger_event(&mut self, port: u8, ports: &mut [u8], memory: &Memory) {
+        let auto = ports[0x06];
+        let x = peek_u16(ports, 0x08);
+        let y = peek_u16(ports, 0x0a);
+        match port {
+            // Width and height
+            0x03 | 0x05 => {
+                let width = peek_u16(ports, 0x02);
+                let height = peek_u16(ports, 0x04);
+                self.resize(width, height, ports);
+            }
+            // Pixel
+            0x0e => {
+                let control = ports[0x0e];
+                let color = control & 0x03;
+                if control & 0x80 != 0x00 {
+                    self.fill(control, x, y, color);
+                } else {
+                    self.put(control, x, y, color);
+                    if auto & 0x01 != 0x00 {
+                        poke_u16(ports, 0x08, x.wrapping_add(1));
+                    }
+                    if auto & 0x02 != 0x00 {
+                        poke_u16(ports, 0x0a, y.wrapping_add(1));
+                    }
+                }
+            }
+            // Sprite
+            0x0f => {
+                let control = ports[0x0f];
+                let two_bpp = control & 0x80 != 0x00;
+                let length = auto >> 4;
+                let mut addr = peek_u16(ports, 0x0c);
+                let addr_step = if auto & 0x04 != 0x00 {
+                    8 << two_bpp as u16
+                } else {
+                    0
+                };
+                let flip_x = control & 0x10 != 0x00;
+                let flip_y = control & 0x20 != 0x00;
+                let step = |auto_bit: u8, flip: bool| match (auto & auto_bit != 0x00, flip) {
+                    (false, _) => 0u16,
+                    (true, false) => 8,
+                    (true, true) => 8u16.wrapping_neg(),
+                };
+                // Repeats are drawn across the direction the position moves in
+                let repeat_x = step(0x02, flip_x);
+                let repeat_y = step(0x01, flip_y);
+                for i in 0..=length as u16 {
+                    let sprite_x = x.wrapping_add(repeat_x.wrapping_mul(i));
+                    let sprite_y = y.wrapping_add(repeat_y.wrapping_mul(i));
+                    self.blit(control, addr, sprite_x, sprite_y, memory);
+                    addr = addr.wrapping_add(addr_step);
+                }
+                if auto & 0x01 != 0x00 {
+                    poke_u16(ports, 0x08, x.wrapping_add(step(0x01, flip_x)));
+                }
+                if auto & 0x02 != 0x00 {
+                    poke_u16(ports, 0x0a, y.wrapping_add(step(0x02, flip_y)));
+                }
+                if auto & 0x04 != 0x00 {
+                    poke_u16(ports, 0x0c, addr);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub(crate) fn save(&self, encoder: &mut Encoder) {
+        encoder.u16(self.width);
+        encoder.u16(self.height);
+        encoder.raw(&self.background);
+        encoder.raw(&self.foreground);
+    }
+
+    pub(crate) fn restore(decoder: &mut Decoder) -> Result<Screen, SnapshotError> {
+        let width = decoder.u16()?;
+        let height = decoder.u16()?;
+        let range = Screen::MIN_SIZE..Screen::MAX_SIZE;
+        if !range.contains(&width) || !range.contains(&height) {
+            return Err(SnapshotError::Corrupt);
+        }
+        let size = width as usize * height as usize;
+        Ok(Screen {
+            width,
+            height,
+            background: decoder.raw(size)?.to_vec(),
+            foreground: decoder.raw(size)?.to_vec(),
+        })
+    }
+}
@@ -1,19 +1,56 @@
+use super::peek_u16;
 use crate::stack::Stack;
+use std::io::Write;
 
-pub fn trigger_event(port: u8, ports: &[u8], wk_stack: &Stack, rt_stack: &Stack) -> Option<u8> {
+pub fn vector(ports: &[u8]) -> Option<u16> {
+    match peek_u16(ports, 0x00) {
+        0 => None,
+        addr => Some(addr),
+    }
+}
+
+/// Decodes the four colours of the Red, Green and Blue ports, one nibble of each port per colour
+/// with colour 0 in the high nibble, into 8-bit RGB.
+pub fn palette(ports: &[u8]) -> [[u8; 3]; 4] {
+    let channels = [
+        peek_u16(ports, 0x08),
+        peek_u16(ports, 0x0a),
+        peek_u16(ports, 0x0c),
+    ];
+    let mut colors = [[0; 3]; 4];
+    for (index, color) in colors.iter_mut().enumerate() {
+        let shift = 12 - index * 4;
+        for (value, channel) in color.iter_mut().zip(channels) {
+            *value = ((channel >> shift) & 0x0f) as u8 * 0x11;
+        }
+    }
+    colors
+}
+
+/// The `System/state` a ROM halted with, if it has.
+pub fn state(ports: &[u8]) -> Option<u8> {
+    match ports[0x0f] {
+        0 => None,
+        state => Some(state),
+    }
+}
+
+pub fn trigger_event(
+    port: u8,
+    ports: &[u8],
+    wk_stack: &Stack,
+    rt_stack: &Stack,
+    error: &mut dyn Write,
+) -> Option<u8> {
     match port {
-        // Red
-        0x08 => {}
-        // Green
-        0x0a => {}
-        // Blue
-        0x0c => {}
-        // Debug
+        // Red, green and blue are decoded by palette whenever the screen is drawn
+        0x08..=0x0d => {}
+        // Debug, written to the error sink as the reference writes it to stderr
         0x0e => {
             let byte = ports[port as usize];
             if byte != 0 {
-                println!("Working stack: {wk_stack}");
-                println!("Return stack: {rt_stack}");
+                writeln!(error, "Working stack: {wk_stack}").ok();
+                writeln!(error, "Return stack: {rt_stack}").ok();
             }
         }
         // State
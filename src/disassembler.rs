@@ -0,0 +1,153 @@
+use crate::{op_codes::OpCode, symbols::Symbols};
+use std::fmt;
+
+/// A decoded instruction and, for literals, its operand.
+#[derive(Clone, Debug)]
+pub struct Instruction {
+    /// Address of the instruction.
+    pub addr: u16,
+    /// The instruction byte followed by any literal operand bytes.
+    pub bytes: Vec<u8>,
+    /// Where a jump lands, when it can be worked out from the literal before it.
+    pub target: Option<u16>,
+}
+
+impl Instruction {
+    /// Decodes the instruction at the start of `bytes`, reading at most the bytes available,
+    /// or returns `None` if `bytes` is empty.
+    pub fn decode(bytes: &[u8], addr: u16) -> Option<Instruction> {
+        let byte = *bytes.first()?;
+        let length = match byte {
+            byte if is_literal(byte) && is_short(byte) => 3,
+            byte if is_literal(byte) => 2,
+            _ => 1,
+        };
+        Some(Instruction {
+            addr,
+            bytes: bytes[0..length.min(bytes.len())].to_vec(),
+            target: None,
+        })
+    }
+
+    /// The instruction byte.
+    pub fn byte(&self) -> u8 {
+        self.bytes[0]
+    }
+
+    /// The instruction byte as an [`OpCode`].
+    pub fn op_code(&self) -> OpCode {
+        OpCode::from(self.byte())
+    }
+
+    /// The value pushed by a literal, unless the ROM ends before all of it.
+    pub fn operand(&self) -> Option<u16> {
+        match (is_short(self.byte()), &self.bytes[1..]) {
+            (false, [byte]) => Some(*byte as u16),
+            (true, [high, low]) => Some(u16::from_be_bytes([*high, *low])),
+            _ => None,
+        }
+    }
+
+    /// Address of the next instruction.
+    pub fn next_addr(&self) -> u16 {
+        self.addr.wrapping_add(self.bytes.len() as u16)
+    }
+
+    /// Works out where a jump goes when it consumes the literal pushed by `previous`.
+    fn resolve_target(&mut self, previous: &Instruction) {
+        let byte = self.byte();
+        let is_jump = matches!(byte & 0b0001_1111, 0x0c..=0x0e);
+        let same_stack = (byte & (1 << 6)) == (previous.byte() & (1 << 6));
+        if !is_jump || !is_literal(previous.byte()) || !same_stack {
+            return;
+        }
+        self.target = match previous.operand() {
+            Some(addr) if is_short(byte) && is_short(previous.byte()) => Some(addr),
+            Some(delta) if !is_short(byte) && !is_short(previous.byte()) => {
+                Some(self.next_addr().wrapping_add(delta as u8 as i8 as u16))
+            }
+            _ => None,
+        };
+    }
+}
+
+/// An instruction formatted with jump targets and literal addresses named after labels.
+pub struct Labelled<'a> {
+    instruction: &'a Instruction,
+    symbols: &'a Symbols,
+}
+
+impl fmt::Display for Labelled<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.instruction.write(f, Some(self.symbols))
+    }
+}
+
+impl Instruction {
+    /// Formats the instruction with jump targets and literal addresses named after labels.
+    pub fn labelled<'a>(&'a self, symbols: &'a Symbols) -> Labelled<'a> {
+        Labelled {
+            instruction: self,
+            symbols,
+        }
+    }
+
+    fn write(&self, f: &mut fmt::Formatter<'_>, symbols: Option<&Symbols>) -> fmt::Result {
+        let bytes = self
+            .bytes
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<Vec<String>>()
+            .join(" ");
+        write!(f, "{:04x}  {bytes:<8}  {}", self.addr, self.op_code())?;
+        match self.bytes[1..] {
+            [byte] => write!(f, " {byte:02x}")?,
+            [high, low] => write!(f, " {high:02x}{low:02x}")?,
+            _ => {}
+        }
+        let label = |addr| symbols.and_then(|symbols| symbols.location(addr));
+        match (self.target, self.operand()) {
+            (Some(target), _) => match label(target) {
+                Some(location) => write!(f, "  ( -> {target:04x} {location} )")?,
+                None => write!(f, "  ( -> {target:04x} )")?,
+            },
+            (None, Some(addr)) if is_short(self.byte()) && addr >= 0x100 => {
+                if let Some(name) = symbols.and_then(|symbols| symbols.name(addr)) {
+                    write!(f, "  ( ;{name} )")?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, None)
+    }
+}
+
+fn is_literal(byte: u8) -> bool {
+    byte & 0b1001_1111 == 0x80
+}
+
+fn is_short(byte: u8) -> bool {
+    (byte & (1 << 5)) != 0x00
+}
+
+/// Decodes `bytes` as instructions, the first of which lives at `start`.
+pub fn disassemble(bytes: &[u8], start: u16) -> Vec<Instruction> {
+    let mut instructions: Vec<Instruction> = vec![];
+    let mut offset = 0;
+    while let Some(mut instruction) =
+        Instruction::decode(&bytes[offset..], start.wrapping_add(offset as u16))
+    {
+        if let Some(previous) = instructions.last() {
+            instruction.resolve_target(previous);
+        }
+        offset += instruction.bytes.len();
+        instructions.push(instruction);
+    }
+    instructions
+}
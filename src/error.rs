@@ -1,10 +1,16 @@
+use crate::{op_codes::OpCode, stack::format_bytes};
 use std::{error::Error, fmt};
 
+/// A fault raised while executing an instruction.
 #[derive(Clone, Debug)]
 pub enum UxnError {
+    /// A pop from an empty stack, or one without enough bytes.
     UnderFlow,
+    /// A push onto a full stack.
     OverFlow,
+    /// A `DIV` by zero.
     DivisionByZero,
+    /// Any other fault.
     Unknown,
 }
 
@@ -22,6 +28,74 @@ impl fmt::Display for UxnError {
 
 impl Error for UxnError {}
 
+/// One of the two stacks of the machine.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StackKind {
+    /// The working stack.
+    Working,
+    /// The return stack.
+    Return,
+}
+
+impl fmt::Display for StackKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StackKind::Working => write!(f, "working stack"),
+            StackKind::Return => write!(f, "return stack"),
+        }
+    }
+}
+
+/// A fault that reached the host because the ROM has no `System/vector` to handle it.
+#[derive(Clone, Debug)]
+pub struct MachineError {
+    /// What went wrong.
+    pub error: UxnError,
+    /// Address of the faulting instruction.
+    pub addr: u16,
+    /// The faulting instruction.
+    pub byte: u8,
+    /// Where the faulting instruction is, when the machine has symbols loaded.
+    pub location: Option<String>,
+    /// The stack that faulted.
+    pub stack: StackKind,
+    /// The working stack when the fault happened, bottom first.
+    pub wk_stack: Vec<u8>,
+    /// The return stack when the fault happened, bottom first.
+    pub rt_stack: Vec<u8>,
+}
+
+impl MachineError {
+    /// The faulting instruction.
+    pub fn op_code(&self) -> OpCode {
+        OpCode::from(self.byte)
+    }
+}
+
+impl fmt::Display for MachineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} on the {}, by {} ({:02x}) at 0x{:04x}",
+            self.error,
+            self.stack,
+            self.op_code(),
+            self.byte,
+            self.addr
+        )?;
+        match &self.location {
+            Some(location) => writeln!(f, " ({location})")?,
+            None => writeln!(f)?,
+        }
+        write!(f, "Working stack: ")?;
+        format_bytes(f, &self.wk_stack)?;
+        write!(f, "\nReturn stack: ")?;
+        format_bytes(f, &self.rt_stack)
+    }
+}
+
+impl Error for MachineError {}
+
 impl From<UxnError> for u8 {
     fn from(error: UxnError) -> Self {
         match error {
@@ -0,0 +1,155 @@
+use minifb::{InputCallback, Key, MouseMode, Window, WindowOptions};
+use std::{cell::RefCell, collections::VecDeque, error::Error, rc::Rc};
+use uxn::{Button, ConsoleInput, Machine, MachineError, MachineEvent, MouseButton};
+
+/// Keyboard input for the Controller device, queued by the window between frames.
+enum KeyInput {
+    Button(Button, bool),
+    Key(u8),
+}
+
+#[derive(Clone, Default)]
+struct KeyQueue {
+    inputs: Rc<RefCell<VecDeque<KeyInput>>>,
+}
+
+impl InputCallback for KeyQueue {
+    fn add_char(&mut self, uni_char: u32) {
+        if let Ok(key) = u8::try_from(uni_char) {
+            self.inputs.borrow_mut().push_back(KeyInput::Key(key));
+        }
+    }
+
+    fn set_key_state(&mut self, key: Key, pressed: bool) {
+        let input = match key {
+            Key::LeftCtrl | Key::RightCtrl => KeyInput::Button(Button::A, pressed),
+            Key::LeftAlt | Key::RightAlt => KeyInput::Button(Button::B, pressed),
+            Key::LeftShift | Key::RightShift => KeyInput::Button(Button::Select, pressed),
+            Key::Home => KeyInput::Button(Button::Start, pressed),
+            Key::Up => KeyInput::Button(Button::Up, pressed),
+            Key::Down => KeyInput::Button(Button::Down, pressed),
+            Key::Left => KeyInput::Button(Button::Left, pressed),
+            Key::Right => KeyInput::Button(Button::Right, pressed),
+            // Control characters are not passed to add_char
+            Key::Backspace if pressed => KeyInput::Key(0x08),
+            Key::Tab if pressed => KeyInput::Key(0x09),
+            Key::Enter if pressed => KeyInput::Key(0x0d),
+            Key::Escape if pressed => KeyInput::Key(0x1b),
+            Key::Delete if pressed => KeyInput::Key(0x7f),
+            _ => return,
+        };
+        self.inputs.borrow_mut().push_back(input);
+    }
+}
+
+/// Opens a window showing the screen scaled up by `scale`, running the screen vector at 60 Hz
+/// and passing console, keyboard and mouse input to the ROM between frames, until the window is closed
+/// or the ROM halts.
+pub fn run(
+    uxn: &mut Machine,
+    input: &mut ConsoleInput,
+    scale: usize,
+) -> Result<u8, Box<dyn Error>> {
+    let keys = KeyQueue::default();
+    let mut window = open(uxn, scale, &keys)?;
+    let mut size = (uxn.devices.screen().width(), uxn.devices.screen().height());
+    let mut buffer = vec![];
+    while window.is_open() {
+        while let Some((byte, console_type)) = input.try_next() {
+            if let MachineEvent::Halt(byte) = uxn.console_input(byte, console_type)? {
+                return Ok(byte);
+            }
+        }
+        let inputs = keys.inputs.take();
+        for input in inputs {
+            let event = match input {
+                KeyInput::Button(button, true) => uxn.press_button(button)?,
+                KeyInput::Button(button, false) => uxn.release_button(button)?,
+                KeyInput::Key(key) => uxn.controller_key(key)?,
+            };
+            if let MachineEvent::Halt(byte) = event {
+                return Ok(byte);
+            }
+        }
+        if let MachineEvent::Halt(byte) = mouse_input(uxn, &window, scale)? {
+            return Ok(byte);
+        }
+        if let MachineEvent::Halt(byte) = uxn.screen_frame()? {
+            return Ok(byte);
+        }
+        let screen = uxn.devices.screen();
+        if size != (screen.width(), screen.height()) {
+            size = (screen.width(), screen.height());
+            window = open(uxn, scale, &keys)?;
+        }
+        render(uxn, scale, &mut buffer);
+        let (width, height) = (size.0 as usize * scale, size.1 as usize * scale);
+        window.update_with_buffer(&buffer, width, height)?;
+    }
+    Ok(0)
+}
+
+/// Passes the pointer position in screen pixels, the buttons and the scroll wheel to the Mouse
+/// device, stopping at the first halt.
+fn mouse_input(
+    uxn: &mut Machine,
+    window: &Window,
+    scale: usize,
+) -> Result<MachineEvent, MachineError> {
+    if let Some((x, y)) = window.get_mouse_pos(MouseMode::Discard) {
+        let (x, y) = (x as usize / scale, y as usize / scale);
+        if let event @ MachineEvent::Halt(_) = uxn.move_mouse(x as u16, y as u16)? {
+            return Ok(event);
+        }
+    }
+    let buttons = [
+        (minifb::MouseButton::Left, MouseButton::Left),
+        (minifb::MouseButton::Middle, MouseButton::Middle),
+        (minifb::MouseButton::Right, MouseButton::Right),
+    ];
+    for (window_button, button) in buttons {
+        let event = match window.get_mouse_down(window_button) {
+            true => uxn.press_mouse_button(button)?,
+            false => uxn.release_mouse_button(button)?,
+        };
+        if let MachineEvent::Halt(_) = event {
+            return Ok(event);
+        }
+    }
+    match window.get_scroll_wheel() {
+        // The wheel reports up as positive, the Mouse device down
+        Some((x, y)) => uxn.scroll_mouse(x.signum() as i16, -y.signum() as i16),
+        None => Ok(MachineEvent::Break),
+    }
+}
+
+fn open(uxn: &Machine, scale: usize, keys: &KeyQueue) -> Result<Window, Box<dyn Error>> {
+    let screen = uxn.devices.screen();
+    let width = screen.width() as usize * scale;
+    let height = screen.height() as usize * scale;
+    let mut window = Window::new("uxn", width, height, WindowOptions::default())?;
+    window.set_target_fps(60);
+    window.set_input_callback(Box::new(keys.clone()));
+    Ok(window)
+}
+
+/// Draws the screen in the System colours into `buffer`, repeating each pixel `scale` times
+/// across and down.
+fn render(uxn: &Machine, scale: usize, buffer: &mut Vec<u32>) {
+    let colors = uxn
+        .devices
+        .palette()
+        .map(|[red, green, blue]| u32::from_be_bytes([0, red, green, blue]));
+    let screen = uxn.devices.screen();
+    let width = screen.width() as usize;
+    buffer.clear();
+    for row in screen.pixels().chunks(width) {
+        let start = buffer.len();
+        for color in row {
+            buffer.extend(std::iter::repeat_n(colors[*color as usize], scale));
+        }
+        for _ in 1..scale {
+            buffer.extend_from_within(start..start + width * scale);
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use crate::{devices::Devices, memory::Memory, stack::Stack};
+use std::collections::VecDeque;
+
+/// What one instruction changed, as the values it overwrote.
+#[derive(Clone, Debug, Default)]
+struct Undo {
+    pc: u16,
+    cycles: u64,
+    memory: Vec<(u16, u8)>,
+    wk_stack: Vec<(u8, u8)>,
+    rt_stack: Vec<(u8, u8)>,
+    ports: Vec<(u8, u8)>,
+}
+
+/// Undo logs for the last few instructions a [`Machine`](crate::Machine) executed, so
+/// [`Machine::step_back`](crate::Machine::step_back) can reverse them. Memory, the stacks,
+/// the device ports and the program counter are restored, but anything a device did on the
+/// host, such as console output or file writes, stays done. Changes the host makes between
+/// instructions, such as console input, are not recorded.
+pub struct History {
+    undos: VecDeque<Undo>,
+    limit: usize,
+    /// The program counter and cycle count the current instruction started from.
+    before: Option<(u16, u64)>,
+}
+
+impl History {
+    /// Keeps undo logs for at most `limit` instructions, dropping the oldest first.
+    pub fn new(limit: usize) -> History {
+        History {
+            undos: VecDeque::with_capacity(limit.min(0x10000)),
+            limit,
+            before: None,
+        }
+    }
+
+    /// How many instructions can be stepped back.
+    pub fn len(&self) -> usize {
+        self.undos.len()
+    }
+
+    /// Whether there is nothing to undo.
+    pub fn is_empty(&self) -> bool {
+        self.undos.is_empty()
+    }
+
+    /// The most steps that are kept.
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Forgets every recorded instruction.
+    pub fn clear(&mut self) {
+        self.undos.clear();
+    }
+
+    /// Notes where an instruction starts and starts logging what it writes.
+    pub(crate) fn begin(
+        &mut self,
+        cycles: u64,
+        memory: &mut Memory,
+        wk_stack: &mut Stack,
+        rt_stack: &mut Stack,
+        devices: &mut Devices,
+    ) {
+        memory.log_writes(true);
+        wk_stack.log_writes(true);
+        rt_stack.log_writes(true);
+        devices.log_writes(true);
+        self.before = Some((memory.pc_value(), cycles));
+    }
+
+    /// Records what the instruction since [`History::begin`] wrote.
+    pub(crate) fn end(
+        &mut self,
+        memory: &mut Memory,
+        wk_stack: &mut Stack,
+        rt_stack: &mut Stack,
+        devices: &mut Devices,
+    ) {
+        let memory_writes = memory.take_write_log();
+        let wk_stack_writes = wk_stack.take_write_log();
+        let rt_stack_writes = rt_stack.take_write_log();
+        let port_writes = devices.take_write_log();
+        memory.log_writes(false);
+        wk_stack.log_writes(false);
+        rt_stack.log_writes(false);
+        devices.log_writes(false);
+        let Some((pc, cycles)) = self.before.take() else {
+            return;
+        };
+        if self.limit == 0 {
+            return;
+        }
+        if self.undos.len() == self.limit {
+            self.undos.pop_front();
+        }
+        self.undos.push_back(Undo {
+            pc,
+            cycles,
+            memory: memory_writes,
+            wk_stack: wk_stack_writes,
+            rt_stack: rt_stack_writes,
+            ports: port_writes,
+        });
+    }
+
+    /// Reverses the most recent instruction, returning its cycle count, or `None` when there
+    /// is nothing left to undo.
+    pub(crate) fn undo(
+        &mut self,
+        memory: &mut Memory,
+        wk_stack: &mut Stack,
+        rt_stack: &mut Stack,
+        devices: &mut Devices,
+    ) -> Option<u64> {
+        let undo = self.undos.pop_back()?;
+        for (addr, byte) in undo.memory.into_iter().rev() {
+            memory.undo_write(addr, byte);
+        }
+        for (index, byte) in undo.wk_stack.into_iter().rev() {
+            wk_stack.undo_write(index, byte);
+        }
+        for (index, byte) in undo.rt_stack.into_iter().rev() {
+            rt_stack.undo_write(index, byte);
+        }
+        for (port, byte) in undo.ports {
+            devices.undo_write(port, byte);
+        }
+        memory.jump(undo.pc);
+        Some(undo.cycles)
+    }
+}
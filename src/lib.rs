@@ -0,0 +1,48 @@
+//! A uxn virtual machine with the Varvara devices.
+//!
+//! ```no_run
+//! use uxn::{Machine, MachineEvent};
+//!
+//! let mut uxn = Machine::new();
+//! uxn.load_rom("roms/exercises/fib.rom").unwrap();
+//! uxn.devices.use_virtual_file_system();
+//! if let MachineEvent::Halt(state) = uxn.run().unwrap() {
+//!     println!("halted with {state:02x}");
+//! }
+//! ```
+mod assembler;
+mod audio_recorder;
+mod console_input;
+mod debugger;
+mod devices;
+mod disassembler;
+mod error;
+mod history;
+mod machine;
+mod memory;
+mod op_codes;
+mod profiler;
+mod snapshot;
+mod stack;
+mod symbols;
+mod terminal_keys;
+mod tracer;
+pub use assembler::{assemble, assemble_source, AssembleError, Assembly, Label};
+pub use audio_recorder::AudioRecorder;
+pub use console_input::ConsoleInput;
+pub use debugger::{Breakpoints, PauseReason, StackCondition};
+pub use devices::{
+    Audio, Button, ConsoleType, DeviceDateTime, DeviceSystemTime, Devices, MouseButton, Screen,
+};
+pub use disassembler::{disassemble, Instruction, Labelled};
+pub use error::{MachineError, StackKind, UxnError};
+pub use history::History;
+pub use machine::{Machine, MachineEvent};
+pub use memory::Memory;
+pub use op_codes::OpCode;
+pub use profiler::{CallSite, Profiler};
+pub use snapshot::SnapshotError;
+pub use stack::Stack;
+pub use symbols::Symbols;
+pub use terminal_keys::{ControllerInput, TerminalKeys};
+pub use tracer::{TraceEntry, Tracer};
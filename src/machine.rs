@@ -1,28 +1,297 @@
-use crate::{devices::Devices, error::UxnError, memory::Memory, stack::Stack};
-use std::{
-    fmt,
-    io::{stdout, Write},
+use crate::{
+    debugger::{Breakpoints, PauseReason},
+    devices::{Button, ConsoleType, Devices, MouseButton},
+    error::{MachineError, StackKind, UxnError},
+    history::History,
+    memory::Memory,
+    profiler::Profiler,
+    snapshot::{Decoder, Encoder, SnapshotError},
+    stack::Stack,
+    symbols::Symbols,
+    tracer::{TraceLine, Tracer},
 };
+use std::{error::Error, fmt, fs, path::Path};
 
+/// A uxn CPU wired to its memory, stacks and Varvara devices.
 #[derive(Default)]
 pub struct Machine {
+    /// Main memory and the program counter.
     pub memory: Memory,
+    /// Device ports and the host side of each device.
     pub devices: Devices,
+    /// The working stack.
     pub wk_stack: Stack,
+    /// The return stack.
     pub rt_stack: Stack,
+    /// Where [`Machine::step`] should pause, along with the watchpoints on `memory` and `devices`.
+    pub breakpoints: Breakpoints,
+    /// Labels used to describe addresses in errors and traces.
+    pub symbols: Symbols,
+    /// Records executed instructions when set.
+    pub tracer: Option<Tracer>,
+    /// Counts executed instructions when set.
+    pub profiler: Option<Profiler>,
+    /// Undo logs for [`Machine::step_back`] when set.
+    pub history: Option<History>,
+    cycles: u64,
+    /// Set while the `System/vector` error handler runs, so a fault inside it goes to the host.
+    handling_fault: bool,
 }
 
+/// A fault inside an instruction, by whether it happened on the stack the instruction works on
+/// or on the other one, which only `JSR` and `STH` push to.
+enum Fault {
+    Source(UxnError),
+    Destination(UxnError),
+}
+
+impl From<UxnError> for Fault {
+    fn from(error: UxnError) -> Fault {
+        Fault::Source(error)
+    }
+}
+
+/// Why a call to [`Machine::run`] returned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum MachineEvent {
+    /// A `BRK` ended the current vector.
     Break,
+    /// The ROM wrote a non-zero value to `System/state`.
     Halt(u8),
+    /// [`Machine::run_for`] ran out of cycles before the vector ended.
+    BudgetExhausted,
+    /// A breakpoint or watchpoint was hit, running again carries on from here.
+    Paused(PauseReason),
 }
 
 impl Machine {
+    /// Creates a machine with empty memory and default devices.
     pub fn new() -> Machine {
         Default::default()
     }
 
-    fn tic(&mut self) -> Result<Option<MachineEvent>, UxnError> {
+    /// Loads a ROM file at the reset vector `0x0100`.
+    pub fn load_rom(&mut self, path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+        self.memory.load_rom(path)
+    }
+
+    /// Copies ROM bytes to the reset vector `0x0100`.
+    pub fn load_bytes(&mut self, bytes: &[u8]) {
+        self.memory.load_bytes(bytes);
+    }
+
+    /// Saves the memory, program counter, stacks, cycle count, whether the error handler is
+    /// running and device state, including the `System/state` of a ROM that halted. Breakpoints,
+    /// symbols, the tracer, the profiler and the console sinks are left out.
+    pub fn snapshot(&self) -> Vec<u8> {
+        let mut encoder = Encoder::new();
+        encoder.u16(self.memory.pc_value());
+        encoder.raw(self.memory.bytes());
+        encoder.raw(self.wk_stack.page());
+        encoder.raw(self.rt_stack.page());
+        encoder.u64(self.cycles);
+        encoder.u8(self.handling_fault.into());
+        self.devices.save(&mut encoder);
+        encoder.finish()
+    }
+
+    /// Restores a [`Machine::snapshot`], leaving the machine untouched if it is not valid.
+    pub fn restore(&mut self, snapshot: &[u8]) -> Result<(), SnapshotError> {
+        let mut decoder = Decoder::new(snapshot)?;
+        let program_counter = decoder.u16()?;
+        let memory = decoder.raw(self.memory.bytes().len())?;
+        let wk_stack = Stack::from_page(decoder.array()?);
+        let rt_stack = Stack::from_page(decoder.array()?);
+        let cycles = decoder.u64()?;
+        let handling_fault = decoder.u8()? != 0;
+        self.devices.restore(&mut decoder)?;
+        self.memory.restore(program_counter, memory);
+        self.wk_stack = wk_stack;
+        self.rt_stack = rt_stack;
+        self.cycles = cycles;
+        self.handling_fault = handling_fault;
+        Ok(())
+    }
+
+    /// Writes a [`Machine::snapshot`] to a file.
+    pub fn save_snapshot(&self, path: impl AsRef<Path>) -> Result<(), SnapshotError> {
+        Ok(fs::write(path, self.snapshot())?)
+    }
+
+    /// Restores a snapshot file written by [`Machine::save_snapshot`].
+    pub fn load_snapshot(&mut self, path: impl AsRef<Path>) -> Result<(), SnapshotError> {
+        self.restore(&fs::read(path)?)
+    }
+
+    /// Jumps to `addr` and runs until the vector ends or the machine halts, pausing straight
+    /// away if there is a breakpoint on `addr`.
+    pub fn run_vector(&mut self, addr: u16) -> Result<MachineEvent, MachineError> {
+        self.memory.jump(addr);
+        if self.breakpoints.contains(addr) {
+            return Ok(MachineEvent::Paused(PauseReason::Breakpoint(addr)));
+        }
+        self.run()
+    }
+
+    /// Runs the reset vector, then passes each argument to the console vector. Stops early on
+    /// anything other than a `BRK`.
+    pub fn boot(&mut self, args: &[impl AsRef<[u8]>]) -> Result<MachineEvent, MachineError> {
+        self.devices.console_argument_count(args.len());
+        match self.run_vector(0x100)? {
+            MachineEvent::Break => {}
+            event => return Ok(event),
+        }
+        for (index, arg) in args.iter().enumerate() {
+            for byte in arg.as_ref() {
+                match self.console_input(*byte, ConsoleType::Argument)? {
+                    MachineEvent::Break => {}
+                    event => return Ok(event),
+                }
+            }
+            let console_type = if index + 1 == args.len() {
+                ConsoleType::EndOfArguments
+            } else {
+                ConsoleType::ArgumentSpacer
+            };
+            match self.console_input(b'\n', console_type)? {
+                MachineEvent::Break => {}
+                event => return Ok(event),
+            }
+        }
+        Ok(MachineEvent::Break)
+    }
+
+    /// Sends a byte to the console vector, doing nothing if the ROM has not set one.
+    pub fn console_input(
+        &mut self,
+        byte: u8,
+        console_type: ConsoleType,
+    ) -> Result<MachineEvent, MachineError> {
+        match self.devices.console_vector() {
+            Some(addr) => {
+                self.devices.console_input(byte, console_type);
+                self.run_vector(addr)
+            }
+            None => Ok(MachineEvent::Break),
+        }
+    }
+
+    /// Presses `button` and runs the controller vector, unless it was already pressed.
+    pub fn press_button(&mut self, button: Button) -> Result<MachineEvent, MachineError> {
+        self.controller_input(|devices| devices.set_button(button, true))
+    }
+
+    /// Releases `button` and runs the controller vector, unless it was not pressed.
+    pub fn release_button(&mut self, button: Button) -> Result<MachineEvent, MachineError> {
+        self.controller_input(|devices| devices.set_button(button, false))
+    }
+
+    /// Sends a typed key to the controller vector, clearing `Controller/key` once it has run.
+    pub fn controller_key(&mut self, key: u8) -> Result<MachineEvent, MachineError> {
+        let event = self.controller_input(|devices| {
+            devices.set_key(key);
+            true
+        });
+        self.devices.set_key(0x00);
+        event
+    }
+
+    /// Updates the Controller ports with `input`, running the controller vector if it reports
+    /// a change and the ROM has set one.
+    fn controller_input(
+        &mut self,
+        input: impl FnOnce(&mut Devices) -> bool,
+    ) -> Result<MachineEvent, MachineError> {
+        match (input(&mut self.devices), self.devices.controller_vector()) {
+            (true, Some(addr)) => self.run_vector(addr),
+            _ => Ok(MachineEvent::Break),
+        }
+    }
+
+    /// Moves the pointer and runs the mouse vector, unless it was already there.
+    pub fn move_mouse(&mut self, x: u16, y: u16) -> Result<MachineEvent, MachineError> {
+        self.mouse_input(|devices| devices.set_mouse_position(x, y))
+    }
+
+    /// Presses a mouse button and runs the mouse vector, unless it was already pressed.
+    pub fn press_mouse_button(
+        &mut self,
+        button: MouseButton,
+    ) -> Result<MachineEvent, MachineError> {
+        self.mouse_input(|devices| devices.set_mouse_button(button, true))
+    }
+
+    /// Releases a mouse button and runs the mouse vector, unless it was not pressed.
+    pub fn release_mouse_button(
+        &mut self,
+        button: MouseButton,
+    ) -> Result<MachineEvent, MachineError> {
+        self.mouse_input(|devices| devices.set_mouse_button(button, false))
+    }
+
+    /// Sends a scroll to the mouse vector, clearing `Mouse/scrollx` and `Mouse/scrolly` once it
+    /// has run.
+    pub fn scroll_mouse(&mut self, x: i16, y: i16) -> Result<MachineEvent, MachineError> {
+        let event = self.mouse_input(|devices| {
+            devices.set_mouse_scroll(x, y);
+            true
+        });
+        self.devices.set_mouse_scroll(0, 0);
+        event
+    }
+
+    /// Updates the Mouse ports with `input`, running the mouse vector if it reports a change
+    /// and the ROM has set one.
+    fn mouse_input(
+        &mut self,
+        input: impl FnOnce(&mut Devices) -> bool,
+    ) -> Result<MachineEvent, MachineError> {
+        match (input(&mut self.devices), self.devices.mouse_vector()) {
+            (true, Some(addr)) => self.run_vector(addr),
+            _ => Ok(MachineEvent::Break),
+        }
+    }
+
+    /// Runs the screen vector once, as the host does every frame, doing nothing if the ROM has
+    /// not set one.
+    pub fn screen_frame(&mut self) -> Result<MachineEvent, MachineError> {
+        match self.devices.screen_vector() {
+            Some(addr) => self.run_vector(addr),
+            None => Ok(MachineEvent::Break),
+        }
+    }
+
+    /// Fills interleaved stereo `samples` with the notes being played, then runs the audio vector
+    /// of each device whose note finished, stopping at the first vector that does not end in a
+    /// `BRK`.
+    pub fn render_audio(&mut self, samples: &mut [i16]) -> Result<MachineEvent, MachineError> {
+        for channel in self.devices.render_audio(samples) {
+            if let Some(addr) = self.devices.audio_vector(channel) {
+                match self.run_vector(addr)? {
+                    MachineEvent::Break => {}
+                    event => return Ok(event),
+                }
+            }
+        }
+        Ok(MachineEvent::Break)
+    }
+
+    /// Executes the instruction at the program counter, reporting a fault along with the stack
+    /// it happened on.
+    fn tic(&mut self) -> Result<Option<MachineEvent>, (UxnError, StackKind)> {
+        let return_mode = (self.memory.current_operation() & (1 << 6)) != 0x00;
+        let (src_kind, dst_kind) = if return_mode {
+            (StackKind::Return, StackKind::Working)
+        } else {
+            (StackKind::Working, StackKind::Return)
+        };
+        self.execute().map_err(|fault| match fault {
+            Fault::Source(error) => (error, src_kind),
+            Fault::Destination(error) => (error, dst_kind),
+        })
+    }
+
+    fn execute(&mut self) -> Result<Option<MachineEvent>, Fault> {
         if self.memory.current_operation() == 0x00 {
             return Ok(Some(MachineEvent::Break));
         }
@@ -344,12 +613,16 @@ impl Machine {
                 if short_mode {
                     let addr = src_stack.pop_u16()?;
                     let program_counter = self.memory.pc_value();
-                    dst_stack.push_u16(program_counter)?;
+                    dst_stack
+                        .push_u16(program_counter)
+                        .map_err(Fault::Destination)?;
                     self.memory.jump(addr);
                 } else {
                     let delta = src_stack.pop_i8()?;
                     let program_counter = self.memory.pc_value();
-                    dst_stack.push_u16(program_counter)?;
+                    dst_stack
+                        .push_u16(program_counter)
+                        .map_err(Fault::Destination)?;
                     self.memory.jump_rel(delta);
                 }
             }
@@ -362,10 +635,10 @@ impl Machine {
             0x0f => {
                 if short_mode {
                     let short = src_stack.pop_u16()?;
-                    dst_stack.push_u16(short)?;
+                    dst_stack.push_u16(short).map_err(Fault::Destination)?;
                 } else {
                     let byte = src_stack.pop_u8()?;
-                    dst_stack.push_u8(byte)?;
+                    dst_stack.push_u8(byte).map_err(Fault::Destination)?;
                 }
             }
             /* Memory Operations */
@@ -577,7 +850,7 @@ impl Machine {
                         Some(short) => src_stack.push_u16(short)?,
                         None => {
                             src_stack.report_divide_by_zero();
-                            return Err(UxnError::DivisionByZero);
+                            return Err(UxnError::DivisionByZero.into());
                         }
                     }
                 } else {
@@ -587,7 +860,7 @@ impl Machine {
                         Some(byte) => src_stack.push_u8(byte)?,
                         None => {
                             src_stack.report_divide_by_zero();
-                            return Err(UxnError::DivisionByZero);
+                            return Err(UxnError::DivisionByZero.into());
                         }
                     }
                 }
@@ -681,17 +954,193 @@ impl Machine {
         Ok(None)
     }
 
-    pub fn run(&mut self) -> Result<MachineEvent, UxnError> {
-        loop {
-            match self.tic()? {
-                None => continue,
-                Some(event) => {
-                    stdout().flush().ok();
-                    return Ok(event);
+    /// Hands a fault to the `System/vector` error handler with the faulting address,
+    /// instruction and error code on the working stack, or to the host if there is none or the
+    /// fault happened inside the handler. The host gets the machine back as it was before the
+    /// instruction, with the program counter on it.
+    fn fault(
+        &mut self,
+        addr: u16,
+        byte: u8,
+        (error, stack): (UxnError, StackKind),
+    ) -> Result<(), MachineError> {
+        match self.devices.system_vector() {
+            Some(vector) if !self.handling_fault => {
+                self.handling_fault = true;
+                self.wk_stack.clear();
+                self.wk_stack.push_u16(addr).ok();
+                self.wk_stack.push_u8(byte).ok();
+                self.wk_stack.push_u8(error.into()).ok();
+                self.memory.jump(vector);
+                Ok(())
+            }
+            _ => {
+                self.handling_fault = false;
+                self.memory.jump(addr);
+                self.devices.flush();
+                let location = self.symbols.location(addr);
+                Err(MachineError {
+                    error,
+                    addr,
+                    byte,
+                    location,
+                    stack,
+                    wk_stack: self.wk_stack.bytes().to_vec(),
+                    rt_stack: self.rt_stack.bytes().to_vec(),
+                })
+            }
+        }
+    }
+
+    /// Executes exactly one instruction, returning an event if it ended the vector, halted the
+    /// machine or hit a breakpoint or watchpoint. A `BRK` is not stepped over, so stepping it
+    /// again returns another break.
+    pub fn step(&mut self) -> Result<Option<MachineEvent>, MachineError> {
+        let addr = self.memory.pc_value();
+        let byte = self.memory.current_operation();
+        let depths = (self.wk_stack.len(), self.rt_stack.len());
+        // Forget watchpoints hit by the host between steps
+        self.memory.take_watch_hit();
+        self.devices.take_watch_hit();
+        self.begin_history();
+        self.cycles = self.cycles.wrapping_add(1);
+        let result = self.tic();
+        self.trace(addr, byte);
+        if let Some(profiler) = &mut self.profiler {
+            let pc = self.memory.pc_value();
+            profiler.record(addr, byte, pc, result.is_ok());
+        }
+        match result {
+            Ok(None) => {
+                self.end_history();
+                Ok(self.check_breakpoints(depths).map(MachineEvent::Paused))
+            }
+            Ok(Some(event)) => {
+                self.handling_fault = false;
+                self.end_history();
+                self.devices.flush();
+                if let Some(tracer) = &mut self.tracer {
+                    tracer.flush();
+                }
+                Ok(Some(event))
+            }
+            Err(error) => {
+                if let Some(tracer) = &mut self.tracer {
+                    tracer.dump(&self.symbols);
                 }
+                // Faults leave the bytes on the stacks alone, so putting back the pointers puts
+                // back the stacks as they were before the instruction
+                self.wk_stack.undo_write(0xff, depths.0 as u8);
+                self.rt_stack.undo_write(0xff, depths.1 as u8);
+                let fault = self.fault(addr, byte, error);
+                self.end_history();
+                fault?;
+                // The error handler may have a breakpoint on its first instruction
+                Ok(self.check_breakpoints(depths).map(MachineEvent::Paused))
             }
         }
     }
+
+    fn begin_history(&mut self) {
+        if let Some(history) = &mut self.history {
+            history.begin(
+                self.cycles,
+                &mut self.memory,
+                &mut self.wk_stack,
+                &mut self.rt_stack,
+                &mut self.devices,
+            );
+        }
+    }
+
+    fn end_history(&mut self) {
+        if let Some(history) = &mut self.history {
+            history.end(
+                &mut self.memory,
+                &mut self.wk_stack,
+                &mut self.rt_stack,
+                &mut self.devices,
+            );
+        }
+    }
+
+    /// Undoes the last instruction recorded in [`Machine::history`], returning whether there
+    /// was one to undo.
+    pub fn step_back(&mut self) -> bool {
+        let Some(history) = &mut self.history else {
+            return false;
+        };
+        let cycles = history.undo(
+            &mut self.memory,
+            &mut self.wk_stack,
+            &mut self.rt_stack,
+            &mut self.devices,
+        );
+        if let Some(cycles) = cycles {
+            self.cycles = cycles;
+        }
+        cycles.is_some()
+    }
+
+    fn trace(&mut self, addr: u16, byte: u8) {
+        if let Some(tracer) = &mut self.tracer {
+            if tracer.traces(addr) {
+                let line = TraceLine {
+                    addr,
+                    byte,
+                    wk_stack: self.wk_stack.bytes(),
+                    rt_stack: self.rt_stack.bytes(),
+                };
+                tracer.record(line, &self.symbols);
+            }
+        }
+    }
+
+    fn check_breakpoints(&mut self, depths: (usize, usize)) -> Option<PauseReason> {
+        let memory_hit = self.memory.take_watch_hit();
+        let port_hit = self.devices.take_watch_hit();
+        if self.breakpoints.is_empty() {
+            return memory_hit.or(port_hit);
+        }
+        let addr = self.memory.pc_value();
+        memory_hit
+            .or(port_hit)
+            .or_else(|| {
+                self.breakpoints
+                    .check_stacks(depths, &self.wk_stack, &self.rt_stack)
+            })
+            .or_else(|| {
+                self.breakpoints
+                    .contains(addr)
+                    .then_some(PauseReason::Breakpoint(addr))
+            })
+    }
+
+    /// Runs from the current program counter until a `BRK` or a halt.
+    pub fn run(&mut self) -> Result<MachineEvent, MachineError> {
+        loop {
+            if let Some(event) = self.step()? {
+                return Ok(event);
+            }
+        }
+    }
+
+    /// Runs like [`Machine::run`], but gives up with [`MachineEvent::BudgetExhausted`] after
+    /// executing `cycles` instructions. Calling `run` again carries on where it stopped.
+    pub fn run_for(&mut self, cycles: u64) -> Result<MachineEvent, MachineError> {
+        for _ in 0..cycles {
+            if let Some(event) = self.step()? {
+                return Ok(event);
+            }
+        }
+        self.devices.flush();
+        Ok(MachineEvent::BudgetExhausted)
+    }
+
+    /// Number of instructions executed since the machine was created.
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
 }
 
 impl fmt::Display for Machine {
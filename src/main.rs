@@ -1,39 +1,404 @@
-mod devices;
-mod error;
-mod machine;
-mod memory;
-mod op_codes;
-mod stack;
+#[cfg(feature = "gui")]
+mod gui;
+mod repl;
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use getch::Getch;
-pub use machine::{Machine, MachineEvent};
-use std::error::Error;
+use repl::Repl;
+use std::{
+    error::Error,
+    fs,
+    io::{self, stderr, stdout, BufWriter, Write},
+    num::ParseIntError,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+use uxn::{
+    assemble, disassemble, AudioRecorder, ConsoleInput, ConsoleType, ControllerInput,
+    DeviceDateTime, History, Machine, MachineError, MachineEvent, Profiler, Symbols, TerminalKeys,
+    Tracer,
+};
 
-fn event_loop(path: &str) -> Result<u8, Box<dyn Error>> {
+#[derive(Clone, Copy, ValueEnum)]
+enum TimeMode {
+    /// Local time of the host
+    Local,
+    /// Coordinated universal time
+    Utc,
+    /// Frozen at --unix-time
+    Static,
+    /// Starts at --unix-time and keeps running
+    Offset,
+}
+
+/// Runs a uxn ROM
+#[derive(Parser)]
+#[command(version, args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    run: Option<RunArgs>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Disassembles a ROM
+    Disasm(DisasmArgs),
+    /// Assembles uxntal source into a ROM and a symbol file
+    Asm(AsmArgs),
+    /// Runs a ROM under an interactive debugger
+    Debug(Box<RunArgs>),
+}
+
+fn parse_addr(string: &str) -> Result<u16, ParseIntError> {
+    let string = string.trim_start_matches("0x");
+    u16::from_str_radix(string, 16)
+}
+
+#[derive(Args)]
+struct DisasmArgs {
+    /// Path to the ROM
+    rom: PathBuf,
+    /// First address to disassemble, in hex
+    #[arg(long, value_name = "ADDR", value_parser = parse_addr, default_value = "0100")]
+    from: u16,
+    /// Address to stop disassembling at, in hex, defaulting to the end of the ROM
+    #[arg(long, value_name = "ADDR", value_parser = parse_addr)]
+    to: Option<u16>,
+    /// Symbol file to label the listing with, defaulting to the ROM path with .sym appended
+    #[arg(long, value_name = "FILE")]
+    sym: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct AsmArgs {
+    /// Path to the uxntal source
+    source: PathBuf,
+    /// Path to write the ROM to, the symbol file is written next to it with a .sym extension
+    rom: PathBuf,
+}
+
+#[derive(Args)]
+struct RunArgs {
+    /// Path to the ROM
+    rom: PathBuf,
+    /// Arguments passed to the ROM through the Console device
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+    args: Vec<String>,
+    /// Give the File devices the host file system under DIR, instead of keeping files in
+    /// memory
+    #[arg(long = "fs-root", value_name = "DIR")]
+    fs_root: Option<PathBuf>,
+    /// Allow the File devices to escape the root directory with ".."
+    #[arg(long = "unsafe-fs", requires = "fs_root")]
+    unsafe_fs: bool,
+    /// Clock used by the DateTime device
+    #[arg(long, value_enum, default_value_t = TimeMode::Local)]
+    time: TimeMode,
+    /// Seconds since the unix epoch, used by the static and offset time modes
+    #[arg(long = "unix-time", value_name = "SECONDS", default_value_t = 0)]
+    unix_time: i64,
+    /// Symbol file used to label errors and breakpoints, defaulting to the ROM path with .sym
+    /// appended
+    #[arg(long, value_name = "FILE")]
+    sym: Option<PathBuf>,
+    /// Write every executed instruction and the stacks it leaves to a file
+    #[arg(long, value_name = "FILE")]
+    trace: Option<PathBuf>,
+    /// Only keep the last COUNT instructions, written to the trace file or stderr on an error
+    #[arg(long = "trace-last", value_name = "COUNT")]
+    trace_last: Option<usize>,
+    /// Only trace instructions between two hex addresses, such as 0100-01ff
+    #[arg(long = "trace-range", value_name = "FROM-TO", value_parser = parse_range)]
+    trace_range: Vec<(u16, u16)>,
+    /// Count executed instructions and print the hottest ones to stderr at the end
+    #[arg(long)]
+    profile: bool,
+    /// How many addresses, labels, opcodes and calls the profile lists
+    #[arg(long = "profile-top", value_name = "COUNT", default_value_t = 10)]
+    profile_top: usize,
+    /// Run the screen vector COUNT times after the reset vector, before reading console input
+    #[arg(long, value_name = "COUNT", default_value_t = 0)]
+    frames: u64,
+    /// Save the screen as a PNG, or a PPM if FILE ends in .ppm, when the ROM halts or stops
+    #[arg(long, value_name = "FILE")]
+    screenshot: Option<PathBuf>,
+    /// Also save the screen every COUNT frames, numbering each file after the frame
+    #[arg(
+        long = "screenshot-every",
+        value_name = "COUNT",
+        requires = "screenshot",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    screenshot_every: Option<u64>,
+    /// Record the audio to a 16-bit stereo WAV file while running the frames, each a 60th of a
+    /// second of emulated time
+    #[arg(long, value_name = "FILE", requires = "frames")]
+    wav: Option<PathBuf>,
+    /// Open a window for the screen, running the screen vector at 60 Hz
+    #[cfg(feature = "gui")]
+    #[arg(long)]
+    gui: bool,
+    /// How many times larger than the screen the window is
+    #[cfg(feature = "gui")]
+    #[arg(
+        long,
+        value_name = "FACTOR",
+        default_value_t = 2,
+        value_parser = clap::value_parser!(u8).range(1..=16)
+    )]
+    scale: u8,
+    /// Keep undo logs for the last COUNT instructions, so the debugger can step back
+    #[arg(long, value_name = "COUNT")]
+    history: Option<usize>,
+    /// Start from a snapshot instead of running the reset vector
+    #[arg(long, value_name = "FILE", conflicts_with = "args")]
+    restore: Option<PathBuf>,
+    /// Save a snapshot when the ROM halts, runs out of input or fails
+    #[arg(long = "save-snapshot", value_name = "FILE")]
+    save_snapshot: Option<PathBuf>,
+}
+
+fn parse_range(string: &str) -> Result<(u16, u16), Box<dyn Error + Send + Sync>> {
+    let (from, to) = string.split_once('-').ok_or("expected FROM-TO")?;
+    Ok((parse_addr(from)?, parse_addr(to)?))
+}
+
+/// Inserts the frame number before the extension, so `screen.png` becomes `screen-00060.png`.
+fn numbered(path: &Path, frame: u64) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{stem}-{frame:05}.{}", extension.to_string_lossy()),
+        None => format!("{stem}-{frame:05}"),
+    };
+    path.with_file_name(name)
+}
+
+fn sym_path(rom: &Path) -> PathBuf {
+    let mut path = rom.as_os_str().to_owned();
+    path.push(".sym");
+    PathBuf::from(path)
+}
+
+/// Loads the given symbol file, or the one next to the ROM if there is one.
+fn load_symbols(rom: &Path, sym: &Option<PathBuf>) -> Result<Symbols, Box<dyn Error>> {
+    match sym {
+        Some(path) => Ok(Symbols::load(path)?),
+        None => Ok(Symbols::load(sym_path(rom)).unwrap_or_default()),
+    }
+}
+
+fn tracer(args: &RunArgs) -> Result<Option<Tracer>, Box<dyn Error>> {
+    let mut tracer = match (&args.trace, args.trace_last) {
+        (None, None) => return Ok(None),
+        (Some(path), None) => Tracer::to_file(path)?,
+        (Some(path), Some(count)) => Tracer::ring_buffer(fs::File::create(path)?, count),
+        (None, Some(count)) => Tracer::ring_buffer(stderr(), count),
+    };
+    for (from, to) in &args.trace_range {
+        tracer.add_range(*from, *to);
+    }
+    Ok(Some(tracer))
+}
+
+fn machine(args: &RunArgs) -> Result<Machine, Box<dyn Error>> {
     let mut uxn = Machine::new();
-    uxn.memory.load_rom(path)?;
-    uxn.devices.use_virtual_file_system();
-    // uxn.devices
-    // .use_file_interface(FileInterface::new_with_path("../", true));
-    let getch = Getch::new();
-    loop {
-        match uxn.run()? {
-            MachineEvent::Break => {
-                if let Some(addr) = uxn.devices.console_vector() {
-                    let byte = getch.getch()?;
-                    uxn.devices.console_event_value(byte);
-                    uxn.memory.jump(addr);
-                }
+    uxn.load_rom(&args.rom)?;
+    uxn.symbols = load_symbols(&args.rom, &args.sym)?;
+    uxn.tracer = tracer(args)?;
+    uxn.history = args.history.map(History::new);
+    if args.profile {
+        uxn.profiler = Some(Profiler::new());
+    }
+    match &args.fs_root {
+        Some(root) => uxn.devices.use_phycial_file_system(root, !args.unsafe_fs),
+        None => uxn.devices.use_virtual_file_system(),
+    }
+    match args.time {
+        TimeMode::Local => uxn.devices.use_local_time(),
+        TimeMode::Utc => uxn.devices.use_utc(),
+        TimeMode::Static => uxn
+            .devices
+            .use_static_time(DeviceDateTime::new(args.unix_time)?),
+        TimeMode::Offset => uxn.devices.set_time(DeviceDateTime::new(args.unix_time)?),
+    }
+    Ok(uxn)
+}
+
+fn event_loop(args: &RunArgs) -> Result<u8, Box<dyn Error>> {
+    let mut uxn = machine(args)?;
+    let result = run(&mut uxn, args);
+    // Failing to save is reported without hiding how the run ended
+    if let Some(path) = &args.screenshot {
+        if let Err(error) = uxn.devices.save_screen(path) {
+            eprintln!("Could not save screenshot {}: {error}", path.display());
+        }
+    }
+    if let Some(path) = &args.save_snapshot {
+        if let Err(error) = uxn.save_snapshot(path) {
+            eprintln!("Could not save snapshot {}: {error}", path.display());
+        }
+    }
+    if let Some(profiler) = &uxn.profiler {
+        if let Err(error) = profiler.write_report(&mut stderr(), &uxn.symbols, args.profile_top) {
+            eprintln!("Could not write profile: {error}");
+        }
+    }
+    result
+}
+
+fn run(uxn: &mut Machine, args: &RunArgs) -> Result<u8, Box<dyn Error>> {
+    let event = match &args.restore {
+        // Carries on with the vector the snapshot was taken in, if any. A snapshot taken after
+        // a fault repeats the faulting instruction, as the machine was left before it
+        Some(path) => {
+            uxn.load_snapshot(path)?;
+            match uxn.devices.halt_state() {
+                Some(state) => MachineEvent::Halt(state),
+                None => uxn.run()?,
+            }
+        }
+        None => uxn.boot(&args.args)?,
+    };
+    if let MachineEvent::Halt(byte) = event {
+        return Ok(byte);
+    }
+    let mut recorder = args.wav.as_ref().map(AudioRecorder::create).transpose()?;
+    let halted = run_frames(uxn, args, recorder.as_mut());
+    if let Some(recorder) = recorder {
+        recorder.finish()?;
+    }
+    if let Some(byte) = halted? {
+        return Ok(byte);
+    }
+    #[cfg(feature = "gui")]
+    if args.gui {
+        return gui::run(uxn, &mut ConsoleInput::stdin(), args.scale.into());
+    }
+    // Unbuffered terminal input, restored when dropped
+    let _terminal = Getch::new();
+    let mut input = ConsoleInput::stdin();
+    let mut keys = TerminalKeys::new();
+    while uxn.devices.console_vector().is_some() || uxn.devices.controller_vector().is_some() {
+        let Some((byte, console_type)) = input.wait() else {
+            break;
+        };
+        if let MachineEvent::Halt(byte) = uxn.console_input(byte, console_type)? {
+            return Ok(byte);
+        }
+        if console_type != ConsoleType::Stdin {
+            continue;
+        }
+        for key in keys.push(byte) {
+            if let MachineEvent::Halt(byte) = controller_input(uxn, key)? {
+                return Ok(byte);
+            }
+        }
+    }
+    Ok(0)
+}
+
+/// Runs the screen vector for each frame, recording the audio it plays and saving numbered
+/// screenshots, returning the halt state if the ROM halts.
+fn run_frames(
+    uxn: &mut Machine,
+    args: &RunArgs,
+    mut recorder: Option<&mut AudioRecorder>,
+) -> Result<Option<u8>, Box<dyn Error>> {
+    for frame in 1..=args.frames {
+        if let MachineEvent::Halt(byte) = uxn.screen_frame()? {
+            return Ok(Some(byte));
+        }
+        if let Some(recorder) = recorder.as_deref_mut() {
+            if let MachineEvent::Halt(byte) = recorder.record_frame(uxn)? {
+                return Ok(Some(byte));
+            }
+        }
+        if let (Some(path), Some(every)) = (&args.screenshot, args.screenshot_every) {
+            if frame % every == 0 {
+                uxn.devices.save_screen(numbered(path, frame))?;
             }
-            MachineEvent::Halt(byte) => return Ok(byte),
         }
     }
+    Ok(None)
+}
+
+/// Passes a key typed in the terminal to the controller vector, pressing and releasing
+/// buttons straight away as terminals do not report releases.
+fn controller_input(uxn: &mut Machine, key: ControllerInput) -> Result<MachineEvent, MachineError> {
+    match key {
+        ControllerInput::Button(button) => match uxn.press_button(button)? {
+            MachineEvent::Halt(byte) => Ok(MachineEvent::Halt(byte)),
+            _ => uxn.release_button(button),
+        },
+        ControllerInput::Key(key) => uxn.controller_key(key),
+    }
+}
+
+fn disasm(args: &DisasmArgs) -> Result<u8, Box<dyn Error>> {
+    let rom = fs::read(&args.rom)?;
+    let length = rom.len().min(0xff00);
+    let mut memory = vec![0; 0x10000];
+    memory[0x100..0x100 + length].copy_from_slice(&rom[..length]);
+    // The end is exclusive, so a ROM filling memory ends at 0x10000
+    let from = args.from as usize;
+    let to = args.to.map_or(0x100 + length, usize::from).max(from);
+    let symbols = load_symbols(&args.rom, &args.sym)?;
+    let mut output = BufWriter::new(stdout().lock());
+    match write_disassembly(&mut output, &memory[from..to], args.from, &symbols) {
+        // The reader went away, such as head after enough lines
+        Err(error) if error.kind() == io::ErrorKind::BrokenPipe => Ok(0),
+        result => Ok(result.map(|()| 0)?),
+    }
+}
+
+fn write_disassembly(
+    output: &mut impl Write,
+    bytes: &[u8],
+    addr: u16,
+    symbols: &Symbols,
+) -> Result<(), io::Error> {
+    for instruction in disassemble(bytes, addr) {
+        if let Some(name) = symbols.name(instruction.addr) {
+            writeln!(output, "@{name}")?;
+        }
+        writeln!(output, "{}", instruction.labelled(symbols))?;
+    }
+    output.flush()
+}
+
+fn asm(args: &AsmArgs) -> Result<u8, Box<dyn Error>> {
+    let assembly = assemble(&args.source)?;
+    fs::write(&args.rom, &assembly.rom)?;
+    fs::write(sym_path(&args.rom), assembly.symbol_file())?;
+    eprintln!(
+        "Assembled {} in {} bytes, {} labels",
+        args.rom.display(),
+        assembly.rom.len(),
+        assembly.labels.len()
+    );
+    Ok(0)
+}
+
+fn debug(args: &RunArgs) -> Result<u8, Box<dyn Error>> {
+    let uxn = machine(args)?;
+    Repl::new(uxn, &args.args).run()
 }
 
-fn main() {
-    match event_loop("roms/devices/file.rom") {
-        Ok(value) => {
-            dbg!(value);
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = match (&cli.command, &cli.run) {
+        (Some(Command::Disasm(args)), _) => disasm(args),
+        (Some(Command::Asm(args)), _) => asm(args),
+        (Some(Command::Debug(args)), _) => debug(args),
+        (None, Some(args)) => event_loop(args),
+        (None, None) => unreachable!("clap requires a ROM without a subcommand"),
+    };
+    match result {
+        Ok(value) => ExitCode::from(value & 0x7f),
+        Err(error) => {
+            eprintln!("{error}");
+            ExitCode::FAILURE
         }
-        Err(error) => eprintln!("{error}"),
     }
 }
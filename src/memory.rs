@@ -1,8 +1,14 @@
-use std::{error::Error, fmt, io::Read, path::Path};
+use crate::debugger::PauseReason;
+use std::{cell::Cell, collections::HashSet, error::Error, fmt, io::Read, path::Path};
 
+/// The 64KB of addressable memory and the program counter.
 pub struct Memory {
     program_counter: u16,
     bytes: [u8; 64 * 1024],
+    read_watches: HashSet<u16>,
+    write_watches: HashSet<u16>,
+    watch_hit: Cell<Option<PauseReason>>,
+    write_log: Option<Vec<(u16, u8)>>,
 }
 
 impl Default for Memory {
@@ -12,6 +18,10 @@ impl Default for Memory {
         Memory {
             program_counter: pc,
             bytes: memory,
+            read_watches: HashSet::new(),
+            write_watches: HashSet::new(),
+            watch_hit: Cell::new(None),
+            write_log: None,
         }
     }
 }
@@ -42,6 +52,65 @@ impl fmt::Display for Memory {
 }
 
 impl Memory {
+    /// Pauses the machine after an instruction reads `addr`.
+    pub fn watch_read(&mut self, addr: u16) {
+        self.read_watches.insert(addr);
+    }
+
+    /// Pauses the machine after an instruction writes to `addr`.
+    pub fn watch_write(&mut self, addr: u16) {
+        self.write_watches.insert(addr);
+    }
+
+    /// Removes the read and write watchpoints on `addr`.
+    pub fn unwatch(&mut self, addr: u16) {
+        self.read_watches.remove(&addr);
+        self.write_watches.remove(&addr);
+    }
+
+    pub(crate) fn take_watch_hit(&self) -> Option<PauseReason> {
+        self.watch_hit.take()
+    }
+
+    #[inline]
+    fn check_read(&self, addr: u16) {
+        if !self.read_watches.is_empty() && self.read_watches.contains(&addr) {
+            self.watch_hit.set(Some(PauseReason::MemoryRead(addr)));
+        }
+    }
+
+    /// Starts or stops logging the old value of every byte written.
+    pub(crate) fn log_writes(&mut self, log: bool) {
+        self.write_log = log.then(Vec::new);
+    }
+
+    pub(crate) fn take_write_log(&mut self) -> Vec<(u16, u8)> {
+        self.write_log
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Puts back a byte overwritten by an instruction, without triggering watchpoints.
+    pub(crate) fn undo_write(&mut self, addr: u16, byte: u8) {
+        self.bytes[addr as usize] = byte;
+    }
+
+    #[inline]
+    fn log_write(&mut self, addr: u16) {
+        if let Some(log) = &mut self.write_log {
+            log.push((addr, self.bytes[addr as usize]));
+        }
+    }
+
+    #[inline]
+    fn check_write(&self, addr: u16) {
+        if !self.write_watches.is_empty() && self.write_watches.contains(&addr) {
+            self.watch_hit.set(Some(PauseReason::MemoryWrite(addr)));
+        }
+    }
+
+    /// Reads a null-terminated string starting at `addr`.
     pub fn get_string(&self, mut addr: u16) -> String {
         let mut string = String::new();
         loop {
@@ -56,6 +125,7 @@ impl Memory {
         string
     }
 
+    /// Reads `length` bytes from `addr`, wrapping around the end of memory.
     pub fn peek_u8s(&self, mut addr: u16, length: u16) -> Vec<u8> {
         let mut bytes = Vec::with_capacity(length as usize);
         for _ in 0..length {
@@ -65,17 +135,34 @@ impl Memory {
         bytes
     }
 
+    /// Writes `bytes` from `addr`, wrapping around the end of memory.
     pub fn poke_u8s(&mut self, mut addr: u16, bytes: &[u8]) {
         for byte in bytes.iter() {
+            self.check_write(addr);
+            self.log_write(addr);
             self.bytes[addr as usize] = *byte;
             addr = addr.wrapping_add(1);
         }
     }
 
+    /// Every byte of memory.
+    pub(crate) fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Replaces every byte of memory and the program counter, leaving watchpoints alone.
+    pub(crate) fn restore(&mut self, program_counter: u16, bytes: &[u8]) {
+        self.bytes.copy_from_slice(bytes);
+        self.program_counter = program_counter;
+    }
+
+    /// Copies ROM bytes to `0x0100`, anything past the end of memory is dropped.
     pub fn load_bytes(&mut self, src: &[u8]) {
-        self.bytes[0x100..0x100 + src.len()].copy_from_slice(src)
+        let len = src.len().min(self.bytes.len() - 0x100);
+        self.bytes[0x100..0x100 + len].copy_from_slice(&src[..len])
     }
 
+    /// Reads a ROM file and copies it to `0x0100`.
     pub fn load_rom(&mut self, path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
         let src = {
             let mut file = std::fs::File::open(path)?;
@@ -87,70 +174,92 @@ impl Memory {
         Ok(())
     }
 
+    /// Reads the byte at `addr`.
     pub fn peek_u8(&self, addr: u16) -> u8 {
+        self.check_read(addr);
         self.bytes[addr as usize]
     }
 
+    /// Reads the big-endian short at `addr`.
     pub fn peek_u16(&self, addr: u16) -> u16 {
+        self.check_read(addr);
+        self.check_read(addr.wrapping_add(1));
         let low = self.bytes[addr as usize];
         let high = self.bytes[addr.wrapping_add(1) as usize];
         u16::from_be_bytes([low, high])
     }
 
+    /// Reads the byte `delta` bytes from the program counter.
     pub fn peek_u8_rel(&self, delta: i8) -> u8 {
         let addr = self.program_counter.wrapping_add(delta as u16);
         self.peek_u8(addr)
     }
 
+    /// Reads the short `delta` bytes from the program counter.
     pub fn peek_u16_rel(&self, delta: i8) -> u16 {
         let addr = self.program_counter.wrapping_add(delta as u16);
         self.peek_u16(addr)
     }
 
-    pub fn next_u8(&mut self) -> u8 {
-        let value = self.peek_u8(self.program_counter);
+    pub(crate) fn next_u8(&mut self) -> u8 {
+        let value = self.bytes[self.program_counter as usize];
         self.jump_rel(1);
         value
     }
 
-    pub fn current_operation(&self) -> u8 {
-        self.peek_u8(self.program_counter)
+    pub(crate) fn current_operation(&self) -> u8 {
+        self.bytes[self.program_counter as usize]
     }
 
-    pub fn next_u16(&mut self) -> u16 {
-        let value = self.peek_u16(self.program_counter);
+    pub(crate) fn next_u16(&mut self) -> u16 {
+        let high = self.bytes[self.program_counter as usize];
+        let low = self.bytes[self.program_counter.wrapping_add(1) as usize];
+        let value = u16::from_be_bytes([high, low]);
         self.jump_rel(2);
         value
     }
 
+    /// Writes `byte` to `addr`.
     pub fn poke_u8(&mut self, addr: u16, byte: u8) {
+        self.check_write(addr);
+        self.log_write(addr);
         self.bytes[addr as usize] = byte;
     }
 
+    /// Writes `short` big-endian to `addr`.
     pub fn poke_u16(&mut self, addr: u16, short: u16) {
+        self.check_write(addr);
+        self.check_write(addr.wrapping_add(1));
+        self.log_write(addr);
+        self.log_write(addr.wrapping_add(1));
         let bytes = short.to_be_bytes();
         self.bytes[addr as usize] = bytes[0];
         self.bytes[addr.wrapping_add(1) as usize] = bytes[1];
     }
 
+    /// Writes the byte `delta` bytes from the program counter.
     pub fn poke_u8_rel(&mut self, delta: i8, byte: u8) {
         let addr = self.program_counter.wrapping_add(delta as u16);
         self.poke_u8(addr, byte);
     }
 
+    /// Writes the short `delta` bytes from the program counter.
     pub fn poke_u16_rel(&mut self, delta: i8, short: u16) {
         let addr = self.program_counter.wrapping_add(delta as u16);
         self.poke_u16(addr, short);
     }
 
+    /// Moves the program counter to `addr`.
     pub fn jump(&mut self, addr: u16) {
         self.program_counter = addr;
     }
 
+    /// Moves the program counter by `delta`.
     pub fn jump_rel(&mut self, delta: i8) {
         self.program_counter = self.program_counter.wrapping_add(delta as u16);
     }
 
+    /// The current program counter.
     pub fn pc_value(&self) -> u16 {
         self.program_counter
     }
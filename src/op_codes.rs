@@ -1,6 +1,8 @@
 use std::fmt;
 
+/// A uxn instruction, one variant per byte value.
 #[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum OpCode {
     BRK,
     INC,
@@ -0,0 +1,210 @@
+use crate::{op_codes::OpCode, symbols::Symbols};
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    io::{self, Write},
+};
+
+/// Cycles spent in subroutines called from one `JSR`, including the `JSR` itself.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CallSite {
+    /// Where the `JSR` is.
+    pub addr: u16,
+    /// The subroutine it called.
+    pub target: u16,
+    /// How many times it was called.
+    pub calls: u64,
+    /// Cycles from each call until the subroutine returned, summed.
+    pub cycles: u64,
+}
+
+/// A subroutine that has not returned yet.
+#[derive(Clone, Copy, Debug)]
+struct Call {
+    addr: u16,
+    target: u16,
+    start: u64,
+}
+
+impl Call {
+    /// Where a jump back from the subroutine lands.
+    fn return_addr(&self) -> u16 {
+        self.addr.wrapping_add(1)
+    }
+}
+
+/// Counts the instructions a [`Machine`](crate::Machine) executes by address and by opcode, and
+/// the cycles spent in subroutines by the `JSR` that called them. Every instruction takes one
+/// cycle.
+#[derive(Clone, Debug)]
+pub struct Profiler {
+    addrs: Box<[u64; 0x10000]>,
+    op_codes: [u64; 256],
+    call_sites: HashMap<(u16, u16), CallSite>,
+    calls: Vec<Call>,
+    cycles: u64,
+}
+
+impl Default for Profiler {
+    fn default() -> Profiler {
+        Profiler {
+            addrs: vec![0; 0x10000].try_into().unwrap(),
+            op_codes: [0; 256],
+            call_sites: HashMap::new(),
+            calls: vec![],
+            cycles: 0,
+        }
+    }
+}
+
+impl Profiler {
+    /// The most subroutines waiting to return that are tracked, beyond which the outermost are
+    /// forgotten, for ROMs that call without ever jumping back.
+    const MAX_CALLS: usize = 0x100;
+
+    /// Creates a profiler with nothing counted yet.
+    pub fn new() -> Profiler {
+        Default::default()
+    }
+
+    /// Instructions executed.
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Executions of each address that ran, in address order.
+    pub fn addrs(&self) -> impl Iterator<Item = (u16, u64)> + '_ {
+        (0..=0xffff)
+            .zip(self.addrs.iter().copied())
+            .filter(|(_, count)| *count > 0)
+    }
+
+    /// Executions of each opcode that ran, in opcode order.
+    pub fn op_codes(&self) -> impl Iterator<Item = (OpCode, u64)> + '_ {
+        (0..=255u8)
+            .zip(self.op_codes.iter().copied())
+            .filter(|(_, count)| *count > 0)
+            .map(|(byte, count)| (OpCode::from(byte), count))
+    }
+
+    /// Cycles spent on instructions under each label, not counting the subroutines they call.
+    /// Instructions before the first label are counted under an empty name.
+    pub fn labels(&self, symbols: &Symbols) -> HashMap<String, u64> {
+        let mut labels = HashMap::new();
+        for (addr, count) in self.addrs() {
+            let name = symbols.locate(addr).map_or("", |(name, _)| name);
+            *labels.entry(name.to_string()).or_default() += count;
+        }
+        labels
+    }
+
+    /// Every `JSR` that returned, in no particular order.
+    pub fn call_sites(&self) -> impl Iterator<Item = &CallSite> + '_ {
+        self.call_sites.values()
+    }
+
+    /// Forgets everything counted so far.
+    pub fn clear(&mut self) {
+        *self = Profiler::new();
+    }
+
+    /// Counts the instruction `byte` at `addr`, with `pc` where it left the program counter.
+    /// Calls and returns are only tracked for instructions that ran without a fault.
+    pub(crate) fn record(&mut self, addr: u16, byte: u8, pc: u16, succeeded: bool) {
+        self.cycles += 1;
+        self.addrs[addr as usize] += 1;
+        self.op_codes[byte as usize] += 1;
+        if !succeeded {
+            return;
+        }
+        match byte & 0b0001_1111 {
+            // JSR in any mode
+            0x0e => {
+                if self.calls.len() == Profiler::MAX_CALLS {
+                    self.calls.remove(0);
+                }
+                self.calls.push(Call {
+                    addr,
+                    target: pc,
+                    start: self.cycles - 1,
+                });
+            }
+            // A JMP or JCN back to where a call came from returns from it, and from any calls
+            // it made that never returned
+            0x0c | 0x0d => {
+                if let Some(index) = self.calls.iter().rposition(|call| call.return_addr() == pc) {
+                    for call in self.calls.drain(index..).rev() {
+                        let call_site =
+                            self.call_sites
+                                .entry((call.addr, call.target))
+                                .or_insert(CallSite {
+                                    addr: call.addr,
+                                    target: call.target,
+                                    ..Default::default()
+                                });
+                        call_site.calls += 1;
+                        call_site.cycles += self.cycles - call.start;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Writes the `count` hottest addresses, labels, opcodes and call sites.
+    pub fn write_report(
+        &self,
+        output: &mut dyn Write,
+        symbols: &Symbols,
+        count: usize,
+    ) -> Result<(), io::Error> {
+        let percent = |cycles: u64| cycles as f64 * 100.0 / self.cycles.max(1) as f64;
+        writeln!(output, "{} cycles", self.cycles)?;
+        writeln!(output, "\nHottest addresses")?;
+        for (addr, cycles) in hottest(self.addrs(), count) {
+            let percent = percent(cycles);
+            let addr = symbols.describe(addr);
+            writeln!(output, "{cycles:>12} {percent:>6.2}%  {addr}")?;
+        }
+        if !symbols.is_empty() {
+            writeln!(output, "\nHottest labels")?;
+            for (name, cycles) in hottest(self.labels(symbols), count) {
+                let percent = percent(cycles);
+                let name = if name.is_empty() {
+                    "(unlabelled)"
+                } else {
+                    &name
+                };
+                writeln!(output, "{cycles:>12} {percent:>6.2}%  {name}")?;
+            }
+        }
+        writeln!(output, "\nHottest opcodes")?;
+        for (op_code, cycles) in hottest(self.op_codes(), count) {
+            let percent = percent(cycles);
+            writeln!(output, "{cycles:>12} {percent:>6.2}%  {op_code}")?;
+        }
+        writeln!(output, "\nHottest calls")?;
+        let call_sites = self
+            .call_sites()
+            .map(|call_site| (call_site, call_site.cycles));
+        for (call_site, cycles) in hottest(call_sites, count) {
+            let percent = percent(cycles);
+            let calls = call_site.calls;
+            let from = symbols.describe(call_site.addr);
+            let to = symbols.describe(call_site.target);
+            writeln!(
+                output,
+                "{cycles:>12} {percent:>6.2}%  {from} -> {to} ({calls}x)"
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The `count` entries with the most cycles, most first.
+fn hottest<T>(entries: impl IntoIterator<Item = (T, u64)>, count: usize) -> Vec<(T, u64)> {
+    let mut entries = entries.into_iter().collect::<Vec<(T, u64)>>();
+    entries.sort_by_key(|(_, cycles)| Reverse(*cycles));
+    entries.truncate(count);
+    entries
+}
@@ -0,0 +1,443 @@
+use std::{
+    collections::VecDeque,
+    error::Error,
+    io::{stdin, stdout, BufRead, Write},
+};
+use uxn::{
+    disassemble, ConsoleType, Instruction, Machine, MachineError, MachineEvent, PauseReason,
+};
+
+static HELP: &str = "\
+step [count]        run one instruction, or count instructions (s)
+next                run one instruction, stepping over subroutine calls (n)
+back [count]        undo one instruction, or count instructions, needs --history
+continue            run until a breakpoint, or until there is no input left (c)
+input <text>        queue a line of console input for the ROM
+eof                 queue the end of console input
+break [addr]        set a breakpoint, or list breakpoints (b)
+delete <addr>       remove a breakpoint or watchpoint
+watch <addr>        pause after memory at addr is written
+rwatch <addr>       pause after memory at addr is read
+pwatch <port>       pause after a device port is written
+stack               show both stacks
+mem <addr> [len]    dump memory (x)
+dis [addr] [count]  disassemble, around the program counter by default (d)
+poke <addr> <value> write a byte, or a short if value has four digits
+save <file>         save a snapshot of the machine
+load <file>         restore a snapshot of the machine
+pc [addr]           show or move the program counter
+help                show this help (h)
+quit                leave the debugger (q)
+Addresses are hex, labels from the symbol file, or labels plus a hex offset.
+Counts and lengths are decimal.";
+
+/// The instructions `next` runs waiting for a subroutine to return before giving up.
+const NEXT_BUDGET: u64 = 1_000_000;
+
+pub struct Repl {
+    uxn: Machine,
+    input: VecDeque<(u8, ConsoleType)>,
+    in_vector: bool,
+    halted: Option<u8>,
+}
+
+impl Repl {
+    pub fn new(mut uxn: Machine, args: &[String]) -> Repl {
+        let mut input = VecDeque::new();
+        for (index, arg) in args.iter().enumerate() {
+            input.extend(arg.bytes().map(|byte| (byte, ConsoleType::Argument)));
+            let console_type = if index + 1 == args.len() {
+                ConsoleType::EndOfArguments
+            } else {
+                ConsoleType::ArgumentSpacer
+            };
+            input.push_back((b'\n', console_type));
+        }
+        uxn.devices.console_argument_count(args.len());
+        uxn.memory.jump(0x100);
+        Repl {
+            uxn,
+            input,
+            in_vector: true,
+            halted: None,
+        }
+    }
+
+    pub fn run(&mut self) -> Result<u8, Box<dyn Error>> {
+        println!("Reset vector, type help for a list of commands");
+        self.show_location();
+        let mut lines = stdin().lock().lines();
+        loop {
+            if let Some(state) = self.halted {
+                println!("Halted with {state:02x}");
+                return Ok(state);
+            }
+            print!("(uxn) ");
+            stdout().flush()?;
+            let Some(line) = lines.next() else {
+                return Ok(0);
+            };
+            let line = line?;
+            let words = line.split_whitespace().collect::<Vec<&str>>();
+            match words.as_slice() {
+                [] => {}
+                ["quit" | "q"] => return Ok(0),
+                ["help" | "h"] => println!("{HELP}"),
+                ["step" | "s"] => self.execute(|repl| repl.step(1)),
+                ["step" | "s", count] => match count.parse() {
+                    Ok(count) => self.execute(|repl| repl.step(count)),
+                    Err(_) => println!("Not a count: {count}"),
+                },
+                ["next" | "n"] => self.execute(Repl::next),
+                ["continue" | "c"] => self.execute(Repl::resume),
+                ["back"] => self.back(1),
+                ["back", count] => match count.parse() {
+                    Ok(count) => self.back(count),
+                    Err(_) => println!("Not a count: {count}"),
+                },
+                ["input", ..] => {
+                    let text = line.trim_start()["input".len()..].trim_start();
+                    self.input
+                        .extend(text.bytes().map(|byte| (byte, ConsoleType::Stdin)));
+                    self.input.push_back((b'\n', ConsoleType::Stdin));
+                }
+                ["eof"] => self.input.push_back((0x00, ConsoleType::EndOfArguments)),
+                ["break" | "b"] => self.list_breakpoints(),
+                ["break" | "b", addr] => {
+                    if let Some(addr) = self.addr(addr) {
+                        self.uxn.breakpoints.add(addr);
+                    }
+                }
+                ["delete", addr] => {
+                    if let Some(addr) = self.addr(addr) {
+                        self.uxn.breakpoints.remove(addr);
+                        self.uxn.memory.unwatch(addr);
+                        if let Ok(port) = u8::try_from(addr) {
+                            self.uxn.devices.unwatch_port(port);
+                        }
+                    }
+                }
+                ["watch", addr] => {
+                    if let Some(addr) = self.addr(addr) {
+                        self.uxn.memory.watch_write(addr);
+                    }
+                }
+                ["rwatch", addr] => {
+                    if let Some(addr) = self.addr(addr) {
+                        self.uxn.memory.watch_read(addr);
+                    }
+                }
+                ["pwatch", port] => match self.addr(port).map(u8::try_from) {
+                    Some(Ok(port)) => self.uxn.devices.watch_port(port),
+                    Some(Err(_)) => println!("Not a device port: {port}"),
+                    None => {}
+                },
+                ["stack"] => {
+                    println!("Working stack: {}", self.uxn.wk_stack);
+                    println!("Return stack: {}", self.uxn.rt_stack);
+                }
+                ["mem" | "x", addr] => self.dump(addr, "64"),
+                ["mem" | "x", addr, length] => self.dump(addr, length),
+                ["dis" | "d"] => self.disassemble_around(),
+                ["dis" | "d", addr] => self.disassemble_from(addr, "8"),
+                ["dis" | "d", addr, count] => self.disassemble_from(addr, count),
+                ["poke", addr, value] => self.poke(addr, value),
+                ["save", path] => match self.uxn.save_snapshot(path) {
+                    Ok(()) => println!("Saved {path}"),
+                    Err(error) => println!("{error}"),
+                },
+                ["load", path] => match self.uxn.load_snapshot(path) {
+                    Ok(()) => {
+                        // The snapshot may have been taken mid-vector, a BRK ends it straight away
+                        self.in_vector = true;
+                        self.show_location();
+                    }
+                    Err(error) => println!("{error}"),
+                },
+                ["pc"] => self.show_location(),
+                ["pc", addr] => {
+                    if let Some(addr) = self.addr(addr) {
+                        self.uxn.memory.jump(addr);
+                        self.in_vector = true;
+                        self.show_location();
+                    }
+                }
+                _ => println!("Unknown command, type help for a list of commands"),
+            }
+        }
+    }
+
+    fn addr(&self, word: &str) -> Option<u16> {
+        let addr = self.uxn.symbols.parse_addr(word);
+        if addr.is_none() {
+            println!("Not an address or label: {word}");
+        }
+        addr
+    }
+
+    fn count(word: &str) -> Option<u16> {
+        let count = word.parse().ok();
+        if count.is_none() {
+            println!("Not a count: {word}");
+        }
+        count
+    }
+
+    fn hex(word: &str) -> Option<u16> {
+        let addr = u16::from_str_radix(word.trim_start_matches("0x"), 16).ok();
+        if addr.is_none() {
+            println!("Not a hex number: {word}");
+        }
+        addr
+    }
+
+    /// Hands the next queued input to the console device, returning the console vector.
+    fn next_vector(&mut self) -> Option<u16> {
+        let Some(addr) = self.uxn.devices.console_vector() else {
+            println!("No vector left to run");
+            return None;
+        };
+        let Some((byte, console_type)) = self.input.pop_front() else {
+            println!("Waiting for console input, queue some with input or eof");
+            return None;
+        };
+        self.uxn.devices.console_input(byte, console_type);
+        Some(addr)
+    }
+
+    /// Starts the next vector if none is running, returning whether one is.
+    fn start_vector(&mut self) -> bool {
+        if !self.in_vector {
+            let Some(addr) = self.next_vector() else {
+                return false;
+            };
+            self.uxn.memory.jump(addr);
+            self.in_vector = true;
+        }
+        true
+    }
+
+    fn handle(&mut self, event: MachineEvent) {
+        match event {
+            MachineEvent::Break => {
+                self.in_vector = false;
+                println!("Vector ended");
+            }
+            MachineEvent::Halt(state) => self.halted = Some(state),
+            MachineEvent::BudgetExhausted => {
+                println!("Gave up after {NEXT_BUDGET} instructions without returning");
+            }
+            MachineEvent::Paused(reason) => match reason {
+                PauseReason::Breakpoint(addr) => println!("Breakpoint at {}", self.label(addr)),
+                PauseReason::MemoryRead(addr) => println!("Read from {}", self.label(addr)),
+                PauseReason::MemoryWrite(addr) => println!("Write to {}", self.label(addr)),
+                PauseReason::PortWrite(port) => println!("Write to port {port:02x}"),
+                PauseReason::StackDepth(stack, depth) => println!("{depth} bytes on the {stack}"),
+            },
+        }
+    }
+
+    /// Runs the machine, reporting a fault rather than leaving the debugger, so it can be
+    /// inspected and stepped back from.
+    fn execute(&mut self, run: impl FnOnce(&mut Repl) -> Result<(), MachineError>) {
+        if let Err(error) = run(self) {
+            println!("{error}");
+            self.show_location();
+        }
+    }
+
+    fn back(&mut self, count: usize) {
+        if self.uxn.history.is_none() {
+            println!("No history is being recorded, start the debugger with --history");
+            return;
+        }
+        for _ in 0..count {
+            if !self.uxn.step_back() {
+                println!("No more history");
+                break;
+            }
+        }
+        self.in_vector = true;
+        self.show_location();
+    }
+
+    fn step(&mut self, count: usize) -> Result<(), MachineError> {
+        for _ in 0..count {
+            if !self.start_vector() {
+                break;
+            }
+            if let Some(event) = self.uxn.step()? {
+                self.handle(event);
+                if !matches!(event, MachineEvent::Paused(_)) {
+                    break;
+                }
+            }
+        }
+        self.show_location();
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<(), MachineError> {
+        if !self.start_vector() {
+            return Ok(());
+        }
+        let pc = self.uxn.memory.pc_value();
+        let is_call = (self.uxn.memory.peek_u8s(pc, 1)[0] & 0b0001_1111) == 0x0e;
+        let event = if is_call {
+            self.step_over(pc.wrapping_add(1))
+        } else {
+            self.uxn.step()
+        };
+        if let Some(event) = event? {
+            self.handle(event);
+        }
+        self.show_location();
+        Ok(())
+    }
+
+    /// Runs the subroutine call at the program counter until it comes back to `return_addr`
+    /// with the return stack no deeper than before, within [`NEXT_BUDGET`] instructions.
+    fn step_over(&mut self, return_addr: u16) -> Result<Option<MachineEvent>, MachineError> {
+        let depth = self.uxn.rt_stack.len();
+        let user_breakpoint = self.uxn.breakpoints.contains(return_addr);
+        self.uxn.breakpoints.add(return_addr);
+        let start = self.uxn.cycles();
+        let result = loop {
+            let spent = self.uxn.cycles() - start;
+            match self.uxn.run_for(NEXT_BUDGET.saturating_sub(spent)) {
+                // A recursive call passing the return address has not returned yet
+                Ok(MachineEvent::Paused(PauseReason::Breakpoint(addr)))
+                    if addr == return_addr
+                        && !user_breakpoint
+                        && self.uxn.rt_stack.len() > depth => {}
+                Ok(event @ MachineEvent::Paused(PauseReason::Breakpoint(addr)))
+                    if addr == return_addr =>
+                {
+                    break Ok(user_breakpoint.then_some(event));
+                }
+                result => break result.map(Some),
+            }
+        };
+        if !user_breakpoint {
+            self.uxn.breakpoints.remove(return_addr);
+        }
+        result
+    }
+
+    fn resume(&mut self) -> Result<(), MachineError> {
+        while self.halted.is_none() {
+            let event = if self.in_vector {
+                self.uxn.run()?
+            } else {
+                let Some(addr) = self.next_vector() else {
+                    break;
+                };
+                self.in_vector = true;
+                self.uxn.run_vector(addr)?
+            };
+            self.handle(event);
+            if matches!(event, MachineEvent::Paused(_)) {
+                self.show_location();
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn list_breakpoints(&self) {
+        let mut addrs = self.uxn.breakpoints.addrs().collect::<Vec<u16>>();
+        addrs.sort();
+        for addr in addrs {
+            println!("{}", self.label(addr));
+        }
+    }
+
+    fn label(&self, addr: u16) -> String {
+        self.uxn.symbols.describe(addr)
+    }
+
+    fn dump(&self, addr: &str, length: &str) {
+        let (Some(addr), Some(length)) = (self.addr(addr), Repl::count(length)) else {
+            return;
+        };
+        let bytes = self.uxn.memory.peek_u8s(addr, length);
+        for (index, row) in bytes.chunks(16).enumerate() {
+            let hex = row
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect::<Vec<String>>()
+                .join(" ");
+            let text = row
+                .iter()
+                .map(|byte| match byte {
+                    0x20..=0x7e => *byte as char,
+                    _ => '.',
+                })
+                .collect::<String>();
+            let row_addr = addr.wrapping_add(index as u16 * 16);
+            println!("{row_addr:04x}  {hex:<47}  {text}");
+        }
+    }
+
+    fn print_instructions(&self, instructions: &[Instruction]) {
+        let pc = self.uxn.memory.pc_value();
+        for instruction in instructions {
+            if let Some(name) = self.uxn.symbols.name(instruction.addr) {
+                println!("      @{name}");
+            }
+            let marker = if instruction.addr == pc { "->" } else { "  " };
+            println!("{marker}  {}", instruction.labelled(&self.uxn.symbols));
+        }
+    }
+
+    fn disassemble_from(&self, addr: &str, count: &str) {
+        let (Some(addr), Some(count)) = (self.addr(addr), Repl::count(count)) else {
+            return;
+        };
+        let bytes = self.uxn.memory.peek_u8s(addr, count.saturating_mul(3));
+        let instructions = disassemble(&bytes, addr);
+        self.print_instructions(&instructions[0..instructions.len().min(count as usize)]);
+    }
+
+    /// Disassembles a few instructions either side of the program counter, starting from the
+    /// furthest back address that decodes into an instruction at the program counter.
+    fn disassemble_around(&self) {
+        let pc = self.uxn.memory.pc_value();
+        let instructions = (1..=12)
+            .rev()
+            .map(|back| {
+                let start = pc.saturating_sub(back);
+                disassemble(&self.uxn.memory.peek_u8s(start, pc - start + 24), start)
+            })
+            .find(|instructions| {
+                instructions
+                    .iter()
+                    .any(|instruction| instruction.addr == pc)
+            })
+            .unwrap_or_else(|| disassemble(&self.uxn.memory.peek_u8s(pc, 24), pc));
+        let index = instructions
+            .iter()
+            .position(|instruction| instruction.addr == pc)
+            .unwrap_or(0);
+        let from = index.saturating_sub(4);
+        let to = (index + 6).min(instructions.len());
+        self.print_instructions(&instructions[from..to]);
+    }
+
+    fn show_location(&self) {
+        let pc = self.uxn.memory.pc_value();
+        let bytes = self.uxn.memory.peek_u8s(pc, 3);
+        self.print_instructions(&disassemble(&bytes, pc)[0..1]);
+    }
+
+    fn poke(&mut self, addr: &str, value: &str) {
+        let (Some(addr), Some(short)) = (self.addr(addr), Repl::hex(value)) else {
+            return;
+        };
+        if value.trim_start_matches("0x").len() > 2 {
+            self.uxn.memory.poke_u16(addr, short);
+        } else {
+            self.uxn.memory.poke_u8(addr, short as u8);
+        }
+    }
+}
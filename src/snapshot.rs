@@ -0,0 +1,157 @@
+use std::{error::Error, fmt, io};
+
+/// Identifies a snapshot file.
+static MAGIC: &[u8; 4] = b"UXNS";
+/// Bumped whenever the snapshot layout changes.
+pub(crate) static VERSION: u16 = 5;
+
+/// Why a snapshot could not be restored.
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// Reading or writing the snapshot failed.
+    Io(io::Error),
+    /// The bytes do not start with the snapshot magic number.
+    NotASnapshot,
+    /// The snapshot was written by a newer or older layout.
+    UnsupportedVersion(u16),
+    /// The snapshot ended early or holds a value that cannot be restored.
+    Corrupt,
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Io(error) => write!(f, "{error}"),
+            SnapshotError::NotASnapshot => write!(f, "Not a snapshot"),
+            SnapshotError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported snapshot version {version}")
+            }
+            SnapshotError::Corrupt => write!(f, "Corrupt snapshot"),
+        }
+    }
+}
+
+impl Error for SnapshotError {}
+
+impl From<io::Error> for SnapshotError {
+    fn from(error: io::Error) -> SnapshotError {
+        SnapshotError::Io(error)
+    }
+}
+
+/// Appends big-endian values to a snapshot.
+pub(crate) struct Encoder {
+    bytes: Vec<u8>,
+}
+
+impl Encoder {
+    /// Starts a snapshot with the magic number and version.
+    pub fn new() -> Encoder {
+        let mut encoder = Encoder { bytes: vec![] };
+        encoder.bytes.extend(MAGIC);
+        encoder.u16(VERSION);
+        encoder
+    }
+
+    pub fn u8(&mut self, byte: u8) {
+        self.bytes.push(byte);
+    }
+
+    pub fn u16(&mut self, short: u16) {
+        self.bytes.extend(short.to_be_bytes());
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.bytes.extend(value.to_be_bytes());
+    }
+
+    pub fn u64(&mut self, value: u64) {
+        self.bytes.extend(value.to_be_bytes());
+    }
+
+    pub fn i64(&mut self, value: i64) {
+        self.bytes.extend(value.to_be_bytes());
+    }
+
+    /// Appends bytes whose length the reader already knows.
+    pub fn raw(&mut self, bytes: &[u8]) {
+        self.bytes.extend(bytes);
+    }
+
+    /// Appends bytes prefixed with their length.
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        self.u32(bytes.len() as u32);
+        self.raw(bytes);
+    }
+
+    pub fn string(&mut self, string: &str) {
+        self.bytes(string.as_bytes());
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+/// Reads back what an [`Encoder`] wrote.
+pub(crate) struct Decoder<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    /// Checks the magic number and version.
+    pub fn new(bytes: &'a [u8]) -> Result<Decoder<'a>, SnapshotError> {
+        let mut decoder = Decoder { bytes };
+        if decoder.raw(MAGIC.len()).ok() != Some(&MAGIC[..]) {
+            return Err(SnapshotError::NotASnapshot);
+        }
+        match decoder.u16()? {
+            version if version == VERSION => Ok(decoder),
+            version => Err(SnapshotError::UnsupportedVersion(version)),
+        }
+    }
+
+    pub fn raw(&mut self, length: usize) -> Result<&'a [u8], SnapshotError> {
+        if length > self.bytes.len() {
+            return Err(SnapshotError::Corrupt);
+        }
+        let (bytes, rest) = self.bytes.split_at(length);
+        self.bytes = rest;
+        Ok(bytes)
+    }
+
+    pub fn array<const N: usize>(&mut self) -> Result<[u8; N], SnapshotError> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.raw(N)?);
+        Ok(array)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, SnapshotError> {
+        Ok(self.raw(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16, SnapshotError> {
+        Ok(u16::from_be_bytes(self.array()?))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, SnapshotError> {
+        Ok(u32::from_be_bytes(self.array()?))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, SnapshotError> {
+        Ok(u64::from_be_bytes(self.array()?))
+    }
+
+    pub fn i64(&mut self) -> Result<i64, SnapshotError> {
+        Ok(i64::from_be_bytes(self.array()?))
+    }
+
+    pub fn bytes(&mut self) -> Result<Vec<u8>, SnapshotError> {
+        let length = self.u32()? as usize;
+        Ok(self.raw(length)?.to_vec())
+    }
+
+    pub fn string(&mut self) -> Result<String, SnapshotError> {
+        String::from_utf8(self.bytes()?).map_err(|_| SnapshotError::Corrupt)
+    }
+}
@@ -4,112 +4,189 @@ use std::fmt;
 static STACK_POINTER_INDEX: usize = 255;
 static ERROR_INDEX: usize = 254;
 
+/// A 256 byte stack page, the last byte of which holds the stack pointer.
+#[derive(Clone, Debug)]
 pub struct Stack {
     page: [u8; 256],
     keep_ptr: Option<u8>,
+    write_log: Option<Vec<(u8, u8)>>,
 }
 
 impl Default for Stack {
     fn default() -> Stack {
         let page = [0; 256];
         let keep_ptr = None;
-        Stack { page, keep_ptr }
+        Stack {
+            page,
+            keep_ptr,
+            write_log: None,
+        }
     }
 }
 
 impl Stack {
+    /// Number of bytes on the stack.
+    pub fn len(&self) -> usize {
+        self.page[STACK_POINTER_INDEX] as usize
+    }
+
+    /// Whether the stack holds no bytes.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The bytes on the stack, bottom first.
+    pub fn bytes(&self) -> &[u8] {
+        &self.page[0..self.len()]
+    }
+
+    /// The whole stack page, including the stack pointer in the last byte.
+    pub(crate) fn page(&self) -> &[u8; 256] {
+        &self.page
+    }
+
+    pub(crate) fn from_page(page: [u8; 256]) -> Stack {
+        Stack {
+            page,
+            keep_ptr: None,
+            write_log: None,
+        }
+    }
+
+    /// Starts or stops logging the old value of every byte of the page written.
+    pub(crate) fn log_writes(&mut self, log: bool) {
+        self.write_log = log.then(Vec::new);
+    }
+
+    pub(crate) fn take_write_log(&mut self) -> Vec<(u8, u8)> {
+        self.write_log
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Puts back a byte of the page, or the stack pointer, overwritten by an instruction.
+    pub(crate) fn undo_write(&mut self, index: u8, byte: u8) {
+        self.page[index as usize] = byte;
+    }
+
+    #[inline]
+    fn write(&mut self, index: usize, byte: u8) {
+        if let Some(log) = &mut self.write_log {
+            log.push((index as u8, self.page[index]));
+        }
+        self.page[index] = byte;
+    }
+
+    fn report(&mut self, error: UxnError) -> UxnError {
+        self.write(ERROR_INDEX, error.clone().into());
+        error
+    }
+
+    /// Removes every byte from the stack.
+    pub fn clear(&mut self) {
+        self.write(STACK_POINTER_INDEX, 0);
+        self.keep_ptr = None;
+    }
+
+    /// Pushes a byte, failing with [`UxnError::OverFlow`] when the stack is full.
     pub fn push_u8(&mut self, byte: u8) -> Result<(), UxnError> {
         let index = self.page[STACK_POINTER_INDEX] as usize;
         if index + 1 == STACK_POINTER_INDEX {
-            let error = UxnError::OverFlow;
-            self.page[ERROR_INDEX] = error.clone().into();
-            Err(error)
+            Err(self.report(UxnError::OverFlow))
         } else {
-            self.page[STACK_POINTER_INDEX] += 1;
-            self.page[index] = byte;
+            self.write(STACK_POINTER_INDEX, index as u8 + 1);
+            self.write(index, byte);
             Ok(())
         }
     }
 
+    /// Pushes `short`, high byte first.
     pub fn push_u16(&mut self, short: u16) -> Result<(), UxnError> {
         let index = self.page[STACK_POINTER_INDEX] as usize;
         if index + 2 >= STACK_POINTER_INDEX {
-            let error = UxnError::OverFlow;
-            self.page[ERROR_INDEX] = error.clone().into();
-            Err(error)
+            Err(self.report(UxnError::OverFlow))
         } else {
-            self.page[STACK_POINTER_INDEX] += 2;
-            let src = short.to_be_bytes();
-            self.page[index..index + 2].copy_from_slice(&src);
+            self.write(STACK_POINTER_INDEX, index as u8 + 2);
+            let [high, low] = short.to_be_bytes();
+            self.write(index, high);
+            self.write(index + 1, low);
             Ok(())
         }
     }
 
     #[inline]
-    fn current_ptr(&mut self) -> &mut u8 {
-        self.keep_ptr
-            .as_mut()
-            .unwrap_or_else(|| &mut self.page[STACK_POINTER_INDEX])
+    fn current_ptr(&self) -> usize {
+        self.keep_ptr.unwrap_or(self.page[STACK_POINTER_INDEX]) as usize
     }
 
+    /// Moves the pointer popped from, which is a copy of the stack pointer in keep mode.
+    #[inline]
+    fn set_current_ptr(&mut self, index: usize) {
+        match &mut self.keep_ptr {
+            Some(keep_ptr) => *keep_ptr = index as u8,
+            None => self.write(STACK_POINTER_INDEX, index as u8),
+        }
+    }
+
+    /// Pops a byte, failing with [`UxnError::UnderFlow`] when the stack is empty.
     pub fn pop_u8(&mut self) -> Result<u8, UxnError> {
-        let ptr = self.current_ptr();
-        let index = *ptr as usize;
+        let index = self.current_ptr();
         if index == 0 {
-            let error = UxnError::UnderFlow;
-            self.page[ERROR_INDEX] = error.clone().into();
-            Err(error)
+            Err(self.report(UxnError::UnderFlow))
         } else {
-            *ptr -= 1;
+            self.set_current_ptr(index - 1);
             let byte = self.page[index - 1];
             Ok(byte)
         }
     }
 
+    /// Pops a byte as a signed offset.
     pub fn pop_i8(&mut self) -> Result<i8, UxnError> {
         Ok(self.pop_u8()? as i8)
     }
 
+    /// Pops a short.
     pub fn pop_u16(&mut self) -> Result<u16, UxnError> {
-        let ptr = self.current_ptr();
-        let index = *ptr as usize;
+        let index = self.current_ptr();
         if index < 2 {
-            let error = UxnError::UnderFlow;
-            self.page[ERROR_INDEX] = error.clone().into();
-            Err(error)
+            Err(self.report(UxnError::UnderFlow))
         } else {
-            *ptr -= 2;
+            self.set_current_ptr(index - 2);
             let short = u16::from_be_bytes([self.page[index - 2], self.page[index - 1]]);
             Ok(short)
         }
     }
 
-    pub fn keep_on(&mut self) {
+    pub(crate) fn keep_on(&mut self) {
         self.keep_ptr = Some(self.page[STACK_POINTER_INDEX]);
     }
 
-    pub fn keep_off(&mut self) {
+    pub(crate) fn keep_off(&mut self) {
         self.keep_ptr = None;
     }
 
-    pub fn report_divide_by_zero(&mut self) {
-        self.page[ERROR_INDEX] = UxnError::DivisionByZero.into();
+    pub(crate) fn report_divide_by_zero(&mut self) {
+        self.report(UxnError::DivisionByZero);
     }
 }
 
+pub(crate) fn format_bytes(f: &mut fmt::Formatter<'_>, bytes: &[u8]) -> fmt::Result {
+    let strings = bytes
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<Vec<String>>();
+    if strings.is_empty() {
+        write!(f, "(empty)")?;
+    } else {
+        let string = strings.join(",");
+        write!(f, "({string})")?;
+    }
+    Ok(())
+}
+
 impl fmt::Display for Stack {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let ptr = self.page[255] as usize;
-        let strings = self.page[0..ptr]
-            .iter()
-            .map(|byte| format!("{byte:02x}"))
-            .collect::<Vec<String>>();
-        if strings.is_empty() {
-            write!(f, "(empty)")?;
-        } else {
-            let string = strings.join(",");
-            write!(f, "({string})")?;
-        }
-        Ok(())
+        format_bytes(f, self.bytes())
     }
 }
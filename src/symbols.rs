@@ -0,0 +1,114 @@
+use crate::assembler::Label;
+use std::{collections::HashMap, io, path::Path};
+
+/// Labels loaded from a symbol file, looked up by name or by address.
+#[derive(Clone, Debug, Default)]
+pub struct Symbols {
+    labels: Vec<Label>,
+    addrs: HashMap<String, u16>,
+}
+
+impl Symbols {
+    /// Parses a symbol file, a big-endian address followed by a null-terminated name for each
+    /// label. A truncated last record is ignored.
+    pub fn from_bytes(bytes: &[u8]) -> Symbols {
+        let mut labels = vec![];
+        let mut rest = bytes;
+        while let [high, low, tail @ ..] = rest {
+            let Some(length) = tail.iter().position(|byte| *byte == 0x00) else {
+                break;
+            };
+            labels.push(Label {
+                name: String::from_utf8_lossy(&tail[0..length]).into_owned(),
+                addr: u16::from_be_bytes([*high, *low]),
+            });
+            rest = &tail[length + 1..];
+        }
+        Symbols::from(labels)
+    }
+
+    /// Reads a symbol file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Symbols, io::Error> {
+        Ok(Symbols::from_bytes(&std::fs::read(path)?))
+    }
+
+    /// The address of the label called `name`.
+    pub fn addr(&self, name: &str) -> Option<u16> {
+        self.addrs.get(name).copied()
+    }
+
+    /// The first label defined at exactly `addr`.
+    pub fn name(&self, addr: u16) -> Option<&str> {
+        let index = self.labels.partition_point(|label| label.addr < addr);
+        match self.labels.get(index) {
+            Some(label) if label.addr == addr => Some(&label.name),
+            _ => None,
+        }
+    }
+
+    /// The closest label at or before `addr` and how far past it `addr` is, preferring the
+    /// last label defined at an address. Zero-page labels never locate addresses past the
+    /// zero-page.
+    pub fn locate(&self, addr: u16) -> Option<(&str, u16)> {
+        let index = self.labels.partition_point(|label| label.addr <= addr);
+        let label = self.labels.get(index.checked_sub(1)?)?;
+        if label.addr < 0x100 && addr >= 0x100 {
+            None
+        } else {
+            Some((&label.name, addr - label.addr))
+        }
+    }
+
+    /// Where `addr` is located, such as `print_str/while+2`, or the first label defined at
+    /// exactly `addr`.
+    pub fn location(&self, addr: u16) -> Option<String> {
+        if let Some(name) = self.name(addr) {
+            return Some(name.to_string());
+        }
+        self.locate(addr).map(|(name, offset)| match offset {
+            0 => name.to_string(),
+            _ => format!("{name}+{offset:x}"),
+        })
+    }
+
+    /// `addr` in hex followed by where it is located, such as `0351 print_str/while+2`.
+    pub fn describe(&self, addr: u16) -> String {
+        match self.location(addr) {
+            Some(location) => format!("{addr:04x} {location}"),
+            None => format!("{addr:04x}"),
+        }
+    }
+
+    /// Parses a label, a label plus a hex offset such as `print_str/while+2`, or a hex address.
+    pub fn parse_addr(&self, word: &str) -> Option<u16> {
+        let parse_hex = |hex: &str| u16::from_str_radix(hex.trim_start_matches("0x"), 16).ok();
+        if let Some(addr) = self.addr(word) {
+            return Some(addr);
+        }
+        match word.rsplit_once('+') {
+            Some((name, offset)) => Some(self.addr(name)?.wrapping_add(parse_hex(offset)?)),
+            None => parse_hex(word),
+        }
+    }
+
+    /// Every label, sorted by address.
+    pub fn labels(&self) -> &[Label] {
+        &self.labels
+    }
+
+    /// Whether there are no labels.
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+}
+
+impl From<Vec<Label>> for Symbols {
+    fn from(mut labels: Vec<Label>) -> Symbols {
+        labels.sort_by_key(|label| label.addr);
+        let addrs = labels
+            .iter()
+            .map(|label| (label.name.clone(), label.addr))
+            .collect();
+        Symbols { labels, addrs }
+    }
+}
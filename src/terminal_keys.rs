@@ -0,0 +1,69 @@
+use crate::devices::Button;
+
+/// What a key typed in a terminal means to the Controller device.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ControllerInput {
+    /// A button was pressed, terminals never report releases so it should be released
+    /// straight away.
+    Button(Button),
+    /// A key was typed, for `Controller/key`.
+    Key(u8),
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum State {
+    #[default]
+    Ground,
+    Escape,
+    Sequence,
+}
+
+/// Turns the bytes a terminal sends into Controller input. The arrow keys press the direction
+/// buttons, Home presses Start, other escape sequences are dropped and every other byte is a
+/// key.
+#[derive(Clone, Debug, Default)]
+pub struct TerminalKeys {
+    state: State,
+}
+
+impl TerminalKeys {
+    /// A decoder with no pending escape sequence.
+    pub fn new() -> TerminalKeys {
+        Default::default()
+    }
+
+    /// Feeds the next byte from the terminal, returning the input it completes. A lone escape
+    /// is only reported once the byte after it arrives.
+    pub fn push(&mut self, byte: u8) -> Vec<ControllerInput> {
+        match (self.state, byte) {
+            (State::Ground, 0x1b) => {
+                self.state = State::Escape;
+                vec![]
+            }
+            (State::Ground, _) => vec![ControllerInput::Key(byte)],
+            (State::Escape, b'[' | b'O') => {
+                self.state = State::Sequence;
+                vec![]
+            }
+            (State::Escape, 0x1b) => vec![ControllerInput::Key(0x1b)],
+            (State::Escape, _) => {
+                self.state = State::Ground;
+                vec![ControllerInput::Key(0x1b), ControllerInput::Key(byte)]
+            }
+            // Parameters of a longer sequence, such as the 1 in ESC [ 1 ~
+            (State::Sequence, 0x30..=0x3f) => vec![],
+            (State::Sequence, _) => {
+                self.state = State::Ground;
+                let button = match byte {
+                    b'A' => Button::Up,
+                    b'B' => Button::Down,
+                    b'C' => Button::Right,
+                    b'D' => Button::Left,
+                    b'H' => Button::Start,
+                    _ => return vec![],
+                };
+                vec![ControllerInput::Button(button)]
+            }
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use crate::{op_codes::OpCode, stack::format_bytes, symbols::Symbols};
+use std::{
+    collections::VecDeque,
+    fmt,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+/// An executed instruction and the stacks it left behind.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceEntry {
+    /// Where the instruction was.
+    pub addr: u16,
+    /// The instruction byte.
+    pub byte: u8,
+    /// The working stack after the instruction ran.
+    pub wk_stack: Vec<u8>,
+    /// The return stack after the instruction ran.
+    pub rt_stack: Vec<u8>,
+}
+
+impl TraceEntry {
+    /// The instruction byte as an [`OpCode`].
+    pub fn op_code(&self) -> OpCode {
+        OpCode::from(self.byte)
+    }
+}
+
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        TraceLine::from(self).fmt(f)
+    }
+}
+
+/// A traced instruction borrowing the machine's stacks, so it can be written out without
+/// copying them.
+#[derive(Clone, Copy)]
+pub(crate) struct TraceLine<'a> {
+    pub addr: u16,
+    pub byte: u8,
+    pub wk_stack: &'a [u8],
+    pub rt_stack: &'a [u8],
+}
+
+impl<'a> From<&'a TraceEntry> for TraceLine<'a> {
+    fn from(entry: &'a TraceEntry) -> TraceLine<'a> {
+        TraceLine {
+            addr: entry.addr,
+            byte: entry.byte,
+            wk_stack: &entry.wk_stack,
+            rt_stack: &entry.rt_stack,
+        }
+    }
+}
+
+impl fmt::Display for TraceLine<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op_code = OpCode::from(self.byte).to_string();
+        write!(f, "{:04x}  {op_code:<6}  wk ", self.addr)?;
+        format_bytes(f, self.wk_stack)?;
+        write!(f, " rt ")?;
+        format_bytes(f, self.rt_stack)
+    }
+}
+
+/// Records the instructions a [`Machine`](crate::Machine) executes, either writing each one out
+/// as it runs or keeping the last few and writing them out when a
+/// [`UxnError`](crate::UxnError) occurs.
+pub struct Tracer {
+    output: Box<dyn Write>,
+    ranges: Vec<(u16, u16)>,
+    ring: Option<(VecDeque<TraceEntry>, usize)>,
+}
+
+impl Tracer {
+    /// Writes every traced instruction to `output`.
+    pub fn new(output: impl Write + 'static) -> Tracer {
+        Tracer {
+            output: Box::new(output),
+            ranges: vec![],
+            ring: None,
+        }
+    }
+
+    /// Writes every traced instruction to a file.
+    pub fn to_file(path: impl AsRef<Path>) -> Result<Tracer, io::Error> {
+        Ok(Tracer::new(BufWriter::new(File::create(path)?)))
+    }
+
+    /// Keeps only the last `size` traced instructions, writing them to `output` when a
+    /// [`UxnError`](crate::UxnError) occurs.
+    pub fn ring_buffer(output: impl Write + 'static, size: usize) -> Tracer {
+        Tracer {
+            output: Box::new(output),
+            ranges: vec![],
+            ring: Some((VecDeque::with_capacity(size), size)),
+        }
+    }
+
+    /// Only traces instructions from `from` to `to` inclusive. Adding several ranges traces
+    /// instructions in any of them, with no ranges every instruction is traced.
+    pub fn add_range(&mut self, from: u16, to: u16) {
+        self.ranges.push((from, to));
+    }
+
+    /// Whether the instruction at `addr` is traced.
+    pub fn traces(&self, addr: u16) -> bool {
+        self.ranges.is_empty()
+            || self
+                .ranges
+                .iter()
+                .any(|(from, to)| (*from..=*to).contains(&addr))
+    }
+
+    /// The instructions held in ring buffer mode, oldest first.
+    pub fn entries(&self) -> impl Iterator<Item = &TraceEntry> + '_ {
+        self.ring.iter().flat_map(|(entries, _)| entries.iter())
+    }
+
+    /// Records an instruction, reusing the oldest entry's stacks once the ring buffer is full.
+    pub(crate) fn record(&mut self, line: TraceLine, symbols: &Symbols) {
+        match &mut self.ring {
+            Some((entries, size)) => {
+                if *size > 0 {
+                    let oldest = (entries.len() == *size).then(|| entries.pop_front());
+                    let mut entry = oldest.flatten().unwrap_or_else(|| TraceEntry {
+                        addr: 0,
+                        byte: 0,
+                        wk_stack: Vec::with_capacity(255),
+                        rt_stack: Vec::with_capacity(255),
+                    });
+                    entry.addr = line.addr;
+                    entry.byte = line.byte;
+                    entry.wk_stack.clear();
+                    entry.wk_stack.extend_from_slice(line.wk_stack);
+                    entry.rt_stack.clear();
+                    entry.rt_stack.extend_from_slice(line.rt_stack);
+                    entries.push_back(entry);
+                }
+            }
+            None => Tracer::write(&mut self.output, line, symbols),
+        }
+    }
+
+    /// Writes out and forgets the ring buffer, called when a [`UxnError`](crate::UxnError)
+    /// occurs.
+    pub(crate) fn dump(&mut self, symbols: &Symbols) {
+        if let Some((entries, _)) = &mut self.ring {
+            for entry in entries.drain(..) {
+                Tracer::write(&mut self.output, TraceLine::from(&entry), symbols);
+            }
+        }
+        self.flush();
+    }
+
+    pub(crate) fn flush(&mut self) {
+        self.output.flush().ok();
+    }
+
+    fn write(output: &mut Box<dyn Write>, line: TraceLine, symbols: &Symbols) {
+        match symbols.location(line.addr) {
+            Some(location) => writeln!(output, "{line}  ( {location} )").ok(),
+            None => writeln!(output, "{line}").ok(),
+        };
+    }
+}
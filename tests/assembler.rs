@@ -0,0 +1,81 @@
+use std::{fs, path::PathBuf};
+use uxn::{assemble, assemble_source};
+
+/// A fresh directory for source files that include each other.
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("uxn-assembler-{name}-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn shipped_sources_assemble_to_their_roms() {
+    for name in [
+        "roms/devices/date_time",
+        "roms/devices/unsafe_file",
+        "roms/file_list",
+    ] {
+        let assembly = assemble(format!("{name}.tal")).unwrap();
+        let rom = fs::read(format!("{name}.rom")).unwrap();
+        assert!(assembly.rom == rom, "{name}.tal does not match {name}.rom");
+    }
+}
+
+#[test]
+fn labels_and_references_resolve() {
+    let assembly = assemble_source("|0100 ;data JMP2 @data 01 ,data", "test.tal").unwrap();
+    assert_eq!(assembly.rom, [0xa0, 0x01, 0x04, 0x2c, 0x01, 0x80, 0xfc]);
+    assert_eq!(assembly.labels[0].name, "data");
+    assert_eq!(assembly.labels[0].addr, 0x0104);
+    assert_eq!(assembly.symbol_file(), b"\x01\x04data\x00");
+}
+
+#[test]
+fn unterminated_comment_is_an_error() {
+    let error = assemble_source("|0100 #01 #02 ADD ( never closed\nBRK", "test.tal").unwrap_err();
+    assert_eq!(error.line, 1);
+    assert!(error.message.starts_with("Unterminated comment"));
+}
+
+#[test]
+fn nested_comments_are_skipped() {
+    let assembly = assemble_source("|0100 ( outer ( inner ) still ) BRK", "test.tal").unwrap();
+    assert_eq!(assembly.rom, [0x00]);
+}
+
+#[test]
+fn file_including_itself_is_an_error() {
+    let dir = scratch_dir("self");
+    let path = dir.join("self.tal");
+    fs::write(&path, "|0100 ~self.tal").unwrap();
+    let error = assemble(&path).unwrap_err();
+    assert!(error.message.starts_with("Include cycle"), "{error}");
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn include_cycle_is_an_error() {
+    let dir = scratch_dir("cycle");
+    fs::write(dir.join("a.tal"), "|0100 ~b.tal").unwrap();
+    fs::write(dir.join("b.tal"), "#01 ~a.tal").unwrap();
+    let error = assemble(dir.join("a.tal")).unwrap_err();
+    assert!(error.message.contains("a.tal -> "), "{error}");
+    assert!(error.message.contains("b.tal -> "), "{error}");
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn file_can_be_included_twice() {
+    let dir = scratch_dir("twice");
+    fs::write(dir.join("main.tal"), "|0100 ~one.tal ~one.tal").unwrap();
+    fs::write(dir.join("one.tal"), "#01").unwrap();
+    let assembly = assemble(dir.join("main.tal")).unwrap();
+    assert_eq!(assembly.rom, [0x80, 0x01, 0x80, 0x01]);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn unknown_label_is_an_error() {
+    let error = assemble_source("|0100 ;nowhere", "test.tal").unwrap_err();
+    assert!(error.message.starts_with("Unknown label"));
+}
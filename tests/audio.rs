@@ -0,0 +1,92 @@
+mod common;
+
+use common::machine;
+use uxn::{Machine, MachineEvent};
+
+/// Plays a four byte sample on the first Audio device, with a vector that counts the notes that
+/// finish in the first byte of memory.
+fn play(adsr: u16, volume: u8, pitch: u8) -> Machine {
+    let (mut uxn, _) = machine(&format!(
+        "|0100 ;on-end #30 DEO2 #{adsr:04x} #38 DEO2 #0004 #3a DEO2 ;sample #3c DEO2
+         #{volume:02x} #3e DEO #{pitch:02x} #3f DEO BRK
+         @on-end #00 LDZ INC #00 STZ BRK
+         @sample 00 40 80 c0"
+    ));
+    assert_eq!(uxn.run().unwrap(), MachineEvent::Break);
+    uxn
+}
+
+/// Renders `frames` stereo frames of audio, running any vectors of notes that finish.
+fn render(uxn: &mut Machine, frames: usize) -> Vec<i16> {
+    let mut samples = vec![0; frames * 2];
+    assert_eq!(uxn.render_audio(&mut samples).unwrap(), MachineEvent::Break);
+    samples
+}
+
+fn output(uxn: &mut Machine) -> u8 {
+    uxn.devices.device_input_u8(0x34)
+}
+
+#[test]
+fn envelope_goes_through_each_stage() {
+    // Each stage lasts 2940 samples
+    let mut uxn = play(0x1111, 0xff, 0x3c);
+    render(&mut uxn, 2900);
+    assert_eq!(output(&mut uxn), 0xff, "attack");
+    render(&mut uxn, 1510);
+    assert_eq!(output(&mut uxn), 0xcc, "decay");
+    render(&mut uxn, 2940);
+    assert_eq!(output(&mut uxn), 0x88, "sustain");
+    render(&mut uxn, 2940);
+    assert_eq!(output(&mut uxn), 0x44, "release");
+    render(&mut uxn, 2000);
+    assert_eq!(output(&mut uxn), 0x00, "released");
+    assert!(!uxn.devices.audio().is_playing());
+}
+
+#[test]
+fn output_follows_the_volume_of_each_side() {
+    let mut uxn = play(0x0000, 0xf0, 0x3c);
+    render(&mut uxn, 10);
+    assert_eq!(output(&mut uxn), 0xf0);
+    let samples = render(&mut uxn, 100);
+    assert!(samples.chunks(2).any(|frame| frame[0] != 0));
+    assert!(samples.chunks(2).all(|frame| frame[1] == 0));
+}
+
+#[test]
+fn looping_note_keeps_playing() {
+    let mut uxn = play(0x0000, 0xff, 0x3c);
+    render(&mut uxn, 1000);
+    assert!(uxn.devices.audio().is_playing());
+    assert_eq!(uxn.memory.peek_u8s(0x0000, 1), [0]);
+}
+
+#[test]
+fn note_without_loop_ends_and_runs_the_vector() {
+    let mut uxn = play(0x0000, 0xff, 0xbc);
+    render(&mut uxn, 1000);
+    assert!(!uxn.devices.audio().is_playing());
+    assert_eq!(uxn.memory.peek_u8s(0x0000, 1), [1]);
+    render(&mut uxn, 1000);
+    assert_eq!(uxn.memory.peek_u8s(0x0000, 1), [1]);
+}
+
+#[test]
+fn position_moves_through_the_sample() {
+    // The four byte sample moves on a byte every 2760704 / 65536 samples at this pitch
+    let mut uxn = play(0x0000, 0xff, 0x3c);
+    render(&mut uxn, 100);
+    assert_eq!(uxn.devices.device_input_u16(0x32), 2);
+    render(&mut uxn, 100);
+    assert_eq!(uxn.devices.device_input_u16(0x32), 0);
+}
+
+#[test]
+fn sample_in_the_zero_page_plays() {
+    let (mut uxn, _) = machine(
+        "|0100 #40 #00 STZ #c0 #01 STZ #0002 #3a DEO2 #0000 #3c DEO2 #ff #3e DEO #3c #3f DEO BRK",
+    );
+    assert_eq!(uxn.run().unwrap(), MachineEvent::Break);
+    assert!(uxn.devices.audio().is_playing());
+}
@@ -0,0 +1,37 @@
+mod common;
+
+use common::rom_machine;
+use std::io::Cursor;
+use uxn::{AudioRecorder, MachineEvent};
+
+/// Records `frames` frames of the audio ROM into an in-memory WAV file.
+fn record(frames: usize) -> Vec<u8> {
+    let mut uxn = rom_machine("roms/devices/audio.rom");
+    uxn.boot(&[] as &[&str]).unwrap();
+    let mut output = Cursor::new(vec![]);
+    let mut recorder = AudioRecorder::new(&mut output).unwrap();
+    for _ in 0..frames {
+        assert_eq!(uxn.screen_frame().unwrap(), MachineEvent::Break);
+        assert_eq!(
+            recorder.record_frame(&mut uxn).unwrap(),
+            MachineEvent::Break
+        );
+    }
+    recorder.finish().unwrap();
+    output.into_inner()
+}
+
+#[test]
+fn recording_is_the_same_every_time() {
+    let wav = record(60);
+    assert!(wav == record(60));
+    let reader = hound::WavReader::new(Cursor::new(&wav)).unwrap();
+    let spec = reader.spec();
+    assert_eq!(spec.channels, 2);
+    assert_eq!(spec.sample_rate, 44100);
+    assert_eq!(spec.bits_per_sample, 16);
+    // A 60th of a second is 735 stereo frames
+    assert_eq!(reader.duration(), 60 * 735);
+    let samples = reader.into_samples::<i16>().map(Result::unwrap);
+    assert!(samples.into_iter().any(|sample| sample != 0));
+}
@@ -0,0 +1,49 @@
+//! Fixtures shared by the integration tests, each of which uses only some of them.
+#![allow(dead_code)]
+
+use std::{cell::RefCell, io::Write, rc::Rc};
+use uxn::{assemble_source, Assembly, DeviceDateTime, Machine};
+
+/// Assembles `source` and loads it into a new machine.
+pub fn machine(source: &str) -> (Machine, Assembly) {
+    let assembly = assemble_source(source, "test.tal").unwrap();
+    let mut uxn = Machine::new();
+    uxn.load_bytes(&assembly.rom);
+    (uxn, assembly)
+}
+
+/// Loads the ROM at `path` into a new machine that keeps files in memory, never sees the clock
+/// move and discards console output, so it runs the same every time.
+pub fn rom_machine(path: &str) -> Machine {
+    let mut uxn = Machine::new();
+    uxn.load_rom(path).unwrap();
+    uxn.devices.use_virtual_file_system();
+    uxn.devices.use_static_time(DeviceDateTime::new(0).unwrap());
+    uxn.devices.set_console_output(std::io::sink());
+    uxn
+}
+
+/// The address of the label `name`.
+pub fn label(assembly: &Assembly, name: &str) -> u16 {
+    assembly
+        .labels
+        .iter()
+        .find(|label| label.name == name)
+        .unwrap()
+        .addr
+}
+
+/// A console sink the test can read back after the machine has written to it.
+#[derive(Clone, Default)]
+pub struct Sink(pub Rc<RefCell<Vec<u8>>>);
+
+impl Write for Sink {
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
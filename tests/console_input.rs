@@ -0,0 +1,35 @@
+use std::{io::Cursor, thread, time::Duration};
+use uxn::{ConsoleInput, ConsoleType};
+
+const EXPECTED: [(u8, ConsoleType); 3] = [
+    (b'a', ConsoleType::Stdin),
+    (b'b', ConsoleType::Stdin),
+    (0x00, ConsoleType::EndOfArguments),
+];
+
+#[test]
+fn wait_delivers_the_bytes_then_one_end_of_input() {
+    let mut input = ConsoleInput::new(Cursor::new(b"ab"));
+    let events = [input.wait(), input.wait(), input.wait()];
+    assert_eq!(events, EXPECTED.map(Some));
+    assert!(input.is_closed());
+    assert_eq!(input.wait(), None);
+    assert_eq!(input.try_next(), None);
+}
+
+#[test]
+fn try_next_delivers_the_same_without_waiting() {
+    let mut input = ConsoleInput::new(Cursor::new(b"ab"));
+    let mut events = vec![];
+    // The stream is read on a background thread, so nothing may be ready yet
+    for _ in 0..1000 {
+        match input.try_next() {
+            Some(event) => events.push(event),
+            None if input.is_closed() => break,
+            None => thread::sleep(Duration::from_millis(1)),
+        }
+    }
+    assert_eq!(events, EXPECTED);
+    assert_eq!(input.try_next(), None);
+    assert_eq!(input.wait(), None);
+}
@@ -0,0 +1,86 @@
+mod common;
+
+use common::machine;
+use uxn::{Button, ControllerInput, Machine, MachineEvent, TerminalKeys};
+
+/// A ROM whose controller vector counts its runs in the first byte of memory and copies
+/// `Controller/key` and `Controller/button` into the next two.
+fn controller() -> Machine {
+    let (mut uxn, _) = machine(
+        "|0100 ;on-controller #80 DEO2 BRK
+         @on-controller #00 LDZ INC #00 STZ #83 DEI #01 STZ #82 DEI #02 STZ BRK",
+    );
+    assert_eq!(uxn.run().unwrap(), MachineEvent::Break);
+    uxn
+}
+
+/// The vector runs, last key and last buttons the vector saw.
+fn seen(uxn: &Machine) -> Vec<u8> {
+    uxn.memory.peek_u8s(0x0000, 3)
+}
+
+#[test]
+fn vector_runs_only_when_the_buttons_change() {
+    let mut uxn = controller();
+    uxn.press_button(Button::A).unwrap();
+    assert_eq!(seen(&uxn), [1, 0x00, 0x01]);
+    uxn.press_button(Button::A).unwrap();
+    assert_eq!(seen(&uxn), [1, 0x00, 0x01]);
+    uxn.press_button(Button::Up).unwrap();
+    assert_eq!(seen(&uxn), [2, 0x00, 0x11]);
+    uxn.release_button(Button::A).unwrap();
+    assert_eq!(seen(&uxn), [3, 0x00, 0x10]);
+    uxn.release_button(Button::A).unwrap();
+    assert_eq!(seen(&uxn), [3, 0x00, 0x10]);
+}
+
+#[test]
+fn key_is_cleared_after_the_vector() {
+    let mut uxn = controller();
+    uxn.controller_key(b'x').unwrap();
+    assert_eq!(seen(&uxn), [1, b'x', 0x00]);
+    assert_eq!(uxn.devices.device_input_u8(0x83), 0x00);
+    // The same key typed again still runs the vector
+    uxn.controller_key(b'x').unwrap();
+    assert_eq!(seen(&uxn), [2, b'x', 0x00]);
+}
+
+fn keys(bytes: &[u8]) -> Vec<ControllerInput> {
+    let mut keys = TerminalKeys::new();
+    bytes.iter().flat_map(|byte| keys.push(*byte)).collect()
+}
+
+#[test]
+fn arrow_keys_and_home_press_buttons() {
+    assert_eq!(
+        keys(b"\x1b[A\x1b[B\x1b[C\x1b[D\x1b[H\x1bOA"),
+        [
+            Button::Up,
+            Button::Down,
+            Button::Right,
+            Button::Left,
+            Button::Start,
+            Button::Up
+        ]
+        .map(ControllerInput::Button)
+    );
+}
+
+#[test]
+fn lone_escape_is_a_key_once_the_next_byte_arrives() {
+    let mut terminal = TerminalKeys::new();
+    assert_eq!(terminal.push(0x1b), []);
+    assert_eq!(
+        terminal.push(b'q'),
+        [ControllerInput::Key(0x1b), ControllerInput::Key(b'q')]
+    );
+    assert_eq!(terminal.push(b'a'), [ControllerInput::Key(b'a')]);
+}
+
+#[test]
+fn other_escape_sequences_are_dropped() {
+    assert_eq!(
+        keys(b"\x1b[1~a\x1b[15;2~b"),
+        [ControllerInput::Key(b'a'), ControllerInput::Key(b'b')]
+    );
+}
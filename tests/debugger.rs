@@ -0,0 +1,86 @@
+mod common;
+
+use common::{label, machine};
+use uxn::{ConsoleType, MachineEvent, PauseReason, StackCondition, StackKind};
+
+fn paused(reason: PauseReason) -> MachineEvent {
+    MachineEvent::Paused(reason)
+}
+
+#[test]
+fn breakpoint_pauses_before_the_instruction() {
+    let (mut uxn, assembly) = machine("|0100 #01 @stop #02 BRK");
+    let stop = label(&assembly, "stop");
+    uxn.breakpoints.add(stop);
+    assert_eq!(uxn.run().unwrap(), paused(PauseReason::Breakpoint(stop)));
+    assert_eq!(uxn.memory.pc_value(), stop);
+    assert_eq!(uxn.wk_stack.bytes(), [0x01]);
+    assert_eq!(uxn.run().unwrap(), MachineEvent::Break);
+}
+
+#[test]
+fn memory_write_pauses_after_the_instruction() {
+    let (mut uxn, assembly) = machine("|0100 #01 #10 STZ @after #0002 #0f STZ2 BRK");
+    uxn.memory.watch_write(0x0010);
+    assert_eq!(uxn.run().unwrap(), paused(PauseReason::MemoryWrite(0x0010)));
+    assert_eq!(uxn.memory.pc_value(), label(&assembly, "after"));
+    // The second byte of a short counts as a write too
+    assert_eq!(uxn.run().unwrap(), paused(PauseReason::MemoryWrite(0x0010)));
+    assert_eq!(uxn.run().unwrap(), MachineEvent::Break);
+}
+
+#[test]
+fn memory_read_pauses_after_the_instruction() {
+    let (mut uxn, _) = machine("|0100 #10 LDZ POP #11 LDZ POP BRK");
+    uxn.memory.watch_read(0x0011);
+    assert_eq!(uxn.run().unwrap(), paused(PauseReason::MemoryRead(0x0011)));
+    assert_eq!(uxn.wk_stack.bytes(), [0x00]);
+    uxn.memory.unwatch(0x0011);
+    assert_eq!(uxn.run().unwrap(), MachineEvent::Break);
+}
+
+#[test]
+fn port_write_pauses_after_the_instruction() {
+    let (mut uxn, _) = machine("|0100 #12 #08 DEO #34 #0a DEO BRK");
+    uxn.devices.watch_port(0x0a);
+    assert_eq!(uxn.run().unwrap(), paused(PauseReason::PortWrite(0x0a)));
+    assert_eq!(uxn.run().unwrap(), MachineEvent::Break);
+}
+
+#[test]
+fn stack_condition_pauses_when_it_starts_to_hold() {
+    let (mut uxn, _) = machine("|0100 #01 #02 #03 #04 POP2 POP2 BRK");
+    let condition = StackCondition::Above(2);
+    uxn.breakpoints
+        .add_stack_condition(StackKind::Working, condition);
+    assert_eq!(
+        uxn.run().unwrap(),
+        paused(PauseReason::StackDepth(StackKind::Working, 3))
+    );
+    assert_eq!(uxn.run().unwrap(), MachineEvent::Break);
+}
+
+#[test]
+fn stack_condition_on_the_return_stack() {
+    // The empty return stack holds the condition from the start, so only emptying it pauses
+    let (mut uxn, _) = machine("|0100 LITr 01 LITr 02 POPr POPr BRK");
+    uxn.breakpoints
+        .add_stack_condition(StackKind::Return, StackCondition::Below(1));
+    assert_eq!(
+        uxn.run().unwrap(),
+        paused(PauseReason::StackDepth(StackKind::Return, 0))
+    );
+    assert_eq!(uxn.run().unwrap(), MachineEvent::Break);
+}
+
+#[test]
+fn host_access_between_steps_does_not_pause() {
+    let (mut uxn, _) = machine("|0100 #01 POP BRK");
+    uxn.memory.watch_write(0x0010);
+    uxn.memory.watch_read(0x0011);
+    uxn.devices.watch_port(0x12);
+    uxn.memory.poke_u8(0x0010, 0xff);
+    uxn.memory.peek_u8(0x0011);
+    uxn.devices.console_input(b'a', ConsoleType::Stdin);
+    assert_eq!(uxn.run().unwrap(), MachineEvent::Break);
+}
@@ -0,0 +1,41 @@
+mod common;
+
+use common::Sink;
+use uxn::{Machine, MachineEvent};
+
+fn machine(source: &str) -> (Machine, Sink, Sink) {
+    let (mut uxn, _) = common::machine(source);
+    let (output, error) = (Sink::default(), Sink::default());
+    uxn.devices.set_console_output(output.clone());
+    uxn.devices.set_console_error(error.clone());
+    (uxn, output, error)
+}
+
+#[test]
+fn console_writes_go_to_their_sinks() {
+    let (mut uxn, output, error) = machine("|0100 LIT \"o #18 DEO LIT \"e #19 DEO BRK");
+    assert_eq!(uxn.run().unwrap(), MachineEvent::Break);
+    assert_eq!(*output.0.borrow(), b"o");
+    assert_eq!(*error.0.borrow(), b"e");
+}
+
+#[test]
+fn system_debug_goes_to_the_error_sink() {
+    let (mut uxn, output, error) = machine("|0100 #12 LITr 34 #010e DEO BRK");
+    assert_eq!(uxn.run().unwrap(), MachineEvent::Break);
+    assert!(output.0.borrow().is_empty());
+    assert_eq!(
+        String::from_utf8(error.0.take()).unwrap(),
+        "Working stack: (12)\nReturn stack: (34)\n"
+    );
+}
+
+#[test]
+fn console_type_holds_the_argument_count_at_boot() {
+    // Halts with Console/type, plus one so no arguments still halts
+    let source = "|0100 #17 DEI INC #0f DEO BRK";
+    for (args, state) in [(&[][..], 0x01), (&["a", "b", "c"][..], 0x04)] {
+        let (mut uxn, _, _) = machine(source);
+        assert_eq!(uxn.boot(args).unwrap(), MachineEvent::Halt(state));
+    }
+}
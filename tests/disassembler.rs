@@ -0,0 +1,88 @@
+mod common;
+
+use common::label;
+use uxn::{assemble_source, disassemble, Assembly, Instruction};
+
+/// The jump targets in the disassembly of `source`, by the address of the jump.
+fn targets(source: &str) -> (Assembly, Vec<(u16, Option<u16>)>) {
+    let assembly = assemble_source(source, "test.tal").unwrap();
+    let targets = disassemble(&assembly.rom, 0x0100)
+        .iter()
+        .filter(|instruction| matches!(instruction.byte() & 0x1f, 0x0c..=0x0e))
+        .map(|instruction| (instruction.addr, instruction.target))
+        .collect();
+    (assembly, targets)
+}
+
+#[test]
+fn relative_jumps_resolve_forwards_and_backwards() {
+    let (assembly, targets) = targets("|0100 @back ,forward JMP BRK @forward ,back JCN BRK");
+    assert_eq!(
+        targets
+            .iter()
+            .map(|(_, target)| *target)
+            .collect::<Vec<_>>(),
+        [
+            Some(label(&assembly, "forward")),
+            Some(label(&assembly, "back"))
+        ]
+    );
+}
+
+#[test]
+fn absolute_jumps_resolve() {
+    let (assembly, targets) = targets("|0100 ;routine JSR2 ;routine JMP2 BRK @routine JMP2r");
+    let routine = label(&assembly, "routine");
+    assert_eq!(targets[0].1, Some(routine));
+    assert_eq!(targets[1].1, Some(routine));
+}
+
+#[test]
+fn jumps_without_a_matching_literal_are_unresolved() {
+    // The literal is on the other stack, the wrong size, or not a literal at all
+    let (_, targets) = targets("|0100 LITr 02 JMP #0004 JMP #02 JMP2 #01 #02 ADD JMP BRK");
+    assert!(targets.iter().all(|(_, target)| target.is_none()));
+}
+
+#[test]
+fn return_mode_jumps_use_return_mode_literals() {
+    let (assembly, targets) = targets("|0100 LIT2r :end JMP2r @end BRK");
+    assert_eq!(targets[0].1, Some(label(&assembly, "end")));
+}
+
+#[test]
+fn instructions_cover_every_byte() {
+    let assembly = assemble_source("|0100 #1234 #56 ADD2 LIT2 ab", "test.tal").unwrap();
+    let instructions = disassemble(&assembly.rom, 0x0100);
+    let length: usize = instructions
+        .iter()
+        .map(|instruction| instruction.bytes.len())
+        .sum();
+    assert_eq!(length, assembly.rom.len());
+    let last = instructions.last().unwrap();
+    assert_eq!(last.bytes, [0xa0, 0xab]);
+    assert_eq!(last.operand(), None);
+}
+
+#[test]
+fn decoding_nothing_gives_nothing() {
+    assert!(Instruction::decode(&[], 0x0100).is_none());
+    let instruction = Instruction::decode(&[0xa0, 0x12, 0x34, 0x00], 0x0100).unwrap();
+    assert_eq!(instruction.operand(), Some(0x1234));
+    assert_eq!(instruction.next_addr(), 0x0103);
+}
+
+#[test]
+fn closed_output_is_not_an_error() {
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_uxn"))
+        .args(["disasm", "roms/beetbug.rom"])
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    // Closing the pipe straight away makes the first write fail, as head does after enough lines
+    drop(child.stdout.take());
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+}
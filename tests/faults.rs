@@ -0,0 +1,85 @@
+mod common;
+
+use common::{label, machine};
+use uxn::{MachineEvent, PauseReason, StackKind, UxnError};
+
+#[test]
+fn fault_reports_the_stacks_before_the_instruction() {
+    let (mut uxn, assembly) = machine("|0100 #0102 #03 @add ADD2 BRK");
+    let error = uxn.run().unwrap_err();
+    assert!(matches!(error.error, UxnError::UnderFlow));
+    assert_eq!(error.addr, label(&assembly, "add"));
+    assert_eq!(error.stack, StackKind::Working);
+    assert_eq!(error.wk_stack, [0x01, 0x02, 0x03]);
+    assert_eq!(uxn.memory.pc_value(), error.addr);
+    assert_eq!(uxn.wk_stack.bytes(), [0x01, 0x02, 0x03]);
+}
+
+#[test]
+fn fault_on_the_other_stack_is_reported_there() {
+    let (mut uxn, _) = machine("|0100 @loop #01 STH ,loop JMP");
+    let error = uxn.run().unwrap_err();
+    assert!(matches!(error.error, UxnError::OverFlow));
+    assert_eq!(error.op_code().to_string(), "STH");
+    assert_eq!(error.stack, StackKind::Return);
+    assert_eq!(error.wk_stack, [0x01]);
+    assert_eq!(error.rt_stack.len(), 254);
+}
+
+#[test]
+fn return_mode_fault_is_on_the_return_stack() {
+    let (mut uxn, _) = machine("|0100 #01 JMP2r");
+    let error = uxn.run().unwrap_err();
+    assert_eq!(error.stack, StackKind::Return);
+    assert_eq!(error.wk_stack, [0x01]);
+}
+
+#[test]
+fn error_handler_gets_the_fault() {
+    let (mut uxn, assembly) = machine(
+        "|0100 ;on-error #00 DEO2 @fault POP BRK
+         @on-error BRK",
+    );
+    assert_eq!(uxn.run().unwrap(), MachineEvent::Break);
+    let fault = label(&assembly, "fault");
+    let [high, low] = fault.to_be_bytes();
+    assert_eq!(uxn.wk_stack.bytes(), [high, low, 0x02, 0x01]);
+}
+
+#[test]
+fn fault_inside_the_error_handler_goes_to_the_host() {
+    let (mut uxn, assembly) = machine(
+        "|0100 ;on-error #00 DEO2 POP BRK
+         @on-error POP2 POP2 @again POP BRK",
+    );
+    let error = uxn.run().unwrap_err();
+    assert!(matches!(error.error, UxnError::UnderFlow));
+    assert_eq!(error.addr, label(&assembly, "again"));
+    assert!(error.wk_stack.is_empty());
+}
+
+#[test]
+fn error_handler_runs_again_after_a_break() {
+    let (mut uxn, assembly) = machine(
+        "|0100 ;on-error #00 DEO2 @fault POP BRK
+         @on-error POP2 POP2 BRK",
+    );
+    assert_eq!(uxn.run().unwrap(), MachineEvent::Break);
+    uxn.memory.jump(label(&assembly, "fault"));
+    assert_eq!(uxn.run().unwrap(), MachineEvent::Break);
+}
+
+#[test]
+fn breakpoint_on_the_error_handler_pauses() {
+    let (mut uxn, assembly) = machine(
+        "|0100 ;on-error #00 DEO2 POP BRK
+         @on-error POP2 POP2 BRK",
+    );
+    let handler = label(&assembly, "on-error");
+    uxn.breakpoints.add(handler);
+    assert_eq!(
+        uxn.run().unwrap(),
+        MachineEvent::Paused(PauseReason::Breakpoint(handler))
+    );
+    assert_eq!(uxn.run().unwrap(), MachineEvent::Break);
+}
@@ -0,0 +1,43 @@
+mod common;
+
+use common::machine;
+use uxn::{History, Machine, MachineEvent};
+
+/// The parts of the machine stepping back puts back.
+#[derive(Debug, PartialEq)]
+struct State {
+    pc: u16,
+    memory: Vec<u8>,
+    wk_stack: Vec<u8>,
+    rt_stack: Vec<u8>,
+    palette: [[u8; 3]; 4],
+}
+
+fn state(uxn: &Machine) -> State {
+    State {
+        pc: uxn.memory.pc_value(),
+        memory: uxn.memory.peek_u8s(0x0000, 0x0200),
+        wk_stack: uxn.wk_stack.bytes().to_vec(),
+        rt_stack: uxn.rt_stack.bytes().to_vec(),
+        palette: uxn.devices.palette(),
+    }
+}
+
+#[test]
+fn stepping_back_undoes_each_instruction() {
+    let (mut uxn, _) =
+        machine("|0100 #1234 #10 STZ2 #03 #04 ADDk POP2 STH #abcd #08 DEO2 INCr BRK");
+    uxn.history = Some(History::new(100));
+    let mut states = vec![];
+    loop {
+        states.push(state(&uxn));
+        if uxn.step().unwrap() == Some(MachineEvent::Break) {
+            break;
+        }
+    }
+    while let Some(expected) = states.pop() {
+        assert!(uxn.step_back());
+        assert_eq!(state(&uxn), expected);
+    }
+    assert!(!uxn.step_back());
+}
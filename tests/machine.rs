@@ -0,0 +1,28 @@
+mod common;
+
+use common::{label, machine};
+use uxn::MachineEvent;
+
+#[test]
+fn run_for_gives_up_and_carries_on() {
+    let (mut uxn, assembly) = machine("|0100 #01 POP @l ,l JMP");
+    let cycles = uxn.cycles();
+    assert_eq!(uxn.run_for(3).unwrap(), MachineEvent::BudgetExhausted);
+    assert_eq!(uxn.cycles(), cycles + 3);
+    // LIT, POP and the LIT of the loop have run
+    let loop_addr = label(&assembly, "l");
+    assert_eq!(uxn.memory.pc_value(), loop_addr + 2);
+    assert_eq!(uxn.run_for(1).unwrap(), MachineEvent::BudgetExhausted);
+    assert_eq!(uxn.memory.pc_value(), loop_addr);
+    assert_eq!(uxn.run_for(1000).unwrap(), MachineEvent::BudgetExhausted);
+    assert_eq!(uxn.cycles(), cycles + 1004);
+    assert_eq!(uxn.memory.pc_value(), loop_addr);
+    assert!(uxn.wk_stack.is_empty());
+}
+
+#[test]
+fn run_for_stops_at_the_end_of_the_vector() {
+    let (mut uxn, _) = machine("|0100 #01 POP BRK");
+    assert_eq!(uxn.run_for(1000).unwrap(), MachineEvent::Break);
+    assert_eq!(uxn.cycles(), 3);
+}
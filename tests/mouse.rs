@@ -0,0 +1,60 @@
+mod common;
+
+use common::machine;
+use uxn::{Machine, MachineEvent, MouseButton};
+
+/// A ROM whose mouse vector counts its runs in the first byte of memory and copies
+/// `Mouse/state`, `Mouse/scrollx` and `Mouse/scrolly` into the bytes after it.
+fn mouse() -> Machine {
+    let (mut uxn, _) = machine(
+        "|0100 ;on-mouse #90 DEO2 BRK
+         @on-mouse #00 LDZ INC #00 STZ #96 DEI #01 STZ #9a DEI2 #02 STZ2 #9c DEI2 #04 STZ2 BRK",
+    );
+    assert_eq!(uxn.run().unwrap(), MachineEvent::Break);
+    uxn
+}
+
+fn runs(uxn: &Machine) -> u8 {
+    uxn.memory.peek_u8(0x0000)
+}
+
+#[test]
+fn vector_runs_only_when_the_position_changes() {
+    let mut uxn = mouse();
+    uxn.move_mouse(10, 20).unwrap();
+    assert_eq!(runs(&uxn), 1);
+    assert_eq!(uxn.devices.device_input_u16(0x92), 10);
+    assert_eq!(uxn.devices.device_input_u16(0x94), 20);
+    uxn.move_mouse(10, 20).unwrap();
+    assert_eq!(runs(&uxn), 1);
+    uxn.move_mouse(10, 21).unwrap();
+    assert_eq!(runs(&uxn), 2);
+}
+
+#[test]
+fn vector_runs_only_when_the_buttons_change() {
+    let mut uxn = mouse();
+    uxn.press_mouse_button(MouseButton::Left).unwrap();
+    uxn.press_mouse_button(MouseButton::Left).unwrap();
+    assert_eq!(runs(&uxn), 1);
+    uxn.press_mouse_button(MouseButton::Right).unwrap();
+    assert_eq!(uxn.memory.peek_u8(0x0001), 0x05);
+    uxn.release_mouse_button(MouseButton::Left).unwrap();
+    uxn.release_mouse_button(MouseButton::Left).unwrap();
+    assert_eq!(runs(&uxn), 3);
+    assert_eq!(uxn.memory.peek_u8(0x0001), 0x04);
+}
+
+#[test]
+fn scroll_is_cleared_after_the_vector() {
+    let mut uxn = mouse();
+    uxn.scroll_mouse(-1, 2).unwrap();
+    assert_eq!(runs(&uxn), 1);
+    assert_eq!(uxn.memory.peek_u16(0x0002), 0xffff);
+    assert_eq!(uxn.memory.peek_u16(0x0004), 0x0002);
+    assert_eq!(uxn.devices.device_input_u16(0x9a), 0);
+    assert_eq!(uxn.devices.device_input_u16(0x9c), 0);
+    // Scrolling the same way again still runs the vector
+    uxn.scroll_mouse(-1, 2).unwrap();
+    assert_eq!(runs(&uxn), 2);
+}
@@ -0,0 +1,35 @@
+mod common;
+
+use common::{label, machine};
+use uxn::{Assembly, Machine, MachineEvent, Profiler};
+
+fn profile(source: &str) -> (Machine, Assembly) {
+    let (mut uxn, assembly) = machine(source);
+    uxn.profiler = Some(Profiler::new());
+    assert_eq!(uxn.run().unwrap(), MachineEvent::Break);
+    (uxn, assembly)
+}
+
+#[test]
+fn calls_are_counted_from_jsr_to_return() {
+    let (uxn, assembly) = profile("|0100 ;sub JSR2 ;sub JSR2 BRK @sub #01 POP JMP2r");
+    let profiler = uxn.profiler.unwrap();
+    let call_sites = profiler.call_sites().collect::<Vec<_>>();
+    assert_eq!(call_sites.len(), 2);
+    for call_site in call_sites {
+        assert_eq!(call_site.target, label(&assembly, "sub"));
+        assert_eq!(call_site.calls, 1);
+        assert_eq!(call_site.cycles, 4);
+    }
+    assert_eq!(profiler.cycles(), 11);
+    assert_eq!(profiler.addrs().next(), Some((0x0100, 1)));
+}
+
+#[test]
+fn faulting_call_is_not_counted() {
+    let (uxn, _) = profile(
+        "|0100 ;on-error #00 DEO2 #01 JSR2 @after BRK
+         @on-error POP2 POP2 ;after JMP2",
+    );
+    assert_eq!(uxn.profiler.unwrap().call_sites().count(), 0);
+}
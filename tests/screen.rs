@@ -0,0 +1,133 @@
+mod common;
+
+use common::{label, machine};
+use uxn::{Machine, MachineEvent};
+
+/// Runs `source` to its `BRK`, returning the machine to look at the screen.
+fn draw(source: &str) -> Machine {
+    let (mut uxn, _) = machine(source);
+    assert_eq!(uxn.run().unwrap(), MachineEvent::Break);
+    uxn
+}
+
+fn pixel(uxn: &Machine, x: u16, y: u16) -> u8 {
+    uxn.devices.screen().pixel(x, y).unwrap()
+}
+
+#[test]
+fn pixels_are_drawn_on_either_layer() {
+    let uxn =
+        draw("|0100 #0003 #28 DEO2 #0004 #2a DEO2 #02 #2e DEO #0005 #28 DEO2 #41 #2e DEO BRK");
+    assert_eq!(pixel(&uxn, 3, 4), 2);
+    assert_eq!(pixel(&uxn, 5, 4), 1);
+    assert_eq!(pixel(&uxn, 4, 4), 0);
+    assert_eq!(uxn.devices.screen().background()[3 + 4 * 0x200], 2);
+    assert_eq!(uxn.devices.screen().foreground()[5 + 4 * 0x200], 1);
+}
+
+#[test]
+fn fill_covers_the_quadrant_chosen_by_the_flip_bits() {
+    let uxn = draw("|0100 #0004 #28 DEO2 #0004 #2a DEO2 #81 #2e DEO BRK");
+    assert_eq!(pixel(&uxn, 4, 4), 1);
+    assert_eq!(pixel(&uxn, 0x1ff, 0x13f), 1);
+    assert_eq!(pixel(&uxn, 3, 4), 0);
+    assert_eq!(pixel(&uxn, 4, 3), 0);
+
+    let uxn = draw("|0100 #0004 #28 DEO2 #0004 #2a DEO2 #b2 #2e DEO BRK");
+    assert_eq!(pixel(&uxn, 0, 0), 2);
+    assert_eq!(pixel(&uxn, 3, 3), 2);
+    assert_eq!(pixel(&uxn, 4, 3), 0);
+    assert_eq!(pixel(&uxn, 3, 4), 0);
+}
+
+#[test]
+fn sprites_are_drawn_in_one_or_two_bits_per_pixel() {
+    let sprite = "@sprite 80 00 00 00 00 00 00 00 c0 00 00 00 00 00 00 00";
+    let uxn = draw(&format!("|0100 ;sprite #2c DEO2 #01 #2f DEO BRK {sprite}"));
+    assert_eq!(pixel(&uxn, 0, 0), 1);
+    assert_eq!(pixel(&uxn, 1, 0), 0);
+    assert_eq!(pixel(&uxn, 0, 8), 0);
+
+    let uxn = draw(&format!("|0100 ;sprite #2c DEO2 #81 #2f DEO BRK {sprite}"));
+    assert_eq!(pixel(&uxn, 0, 0), 3);
+    assert_eq!(pixel(&uxn, 1, 0), 2);
+    assert_eq!(pixel(&uxn, 2, 0), 0);
+}
+
+#[test]
+fn sprites_flip_on_either_axis() {
+    let sprite = "@sprite 80 00 00 00 00 00 00 00";
+    let uxn = draw(&format!("|0100 ;sprite #2c DEO2 #11 #2f DEO BRK {sprite}"));
+    assert_eq!(pixel(&uxn, 7, 0), 1);
+    assert_eq!(pixel(&uxn, 0, 0), 0);
+    let uxn = draw(&format!("|0100 ;sprite #2c DEO2 #21 #2f DEO BRK {sprite}"));
+    assert_eq!(pixel(&uxn, 0, 7), 1);
+    assert_eq!(pixel(&uxn, 0, 0), 0);
+}
+
+#[test]
+fn transparent_blend_modes_skip_colour_zero() {
+    // The background is filled with colour 3, then a sprite with one set pixel is drawn over it
+    for (blend, drawn, behind) in [(0x0, 0, 0), (0x5, 1, 3), (0xa, 2, 3), (0xf, 3, 3)] {
+        let uxn = draw(&format!(
+            "|0100 #83 #2e DEO ;sprite #2c DEO2 #{blend:02x} #2f DEO BRK
+             @sprite 80 00 00 00 00 00 00 00"
+        ));
+        assert_eq!(pixel(&uxn, 0, 0), drawn, "blend {blend:x}");
+        assert_eq!(pixel(&uxn, 1, 0), behind, "blend {blend:x}");
+    }
+}
+
+#[test]
+fn pixel_auto_moves_the_position() {
+    let mut uxn = draw("|0100 #03 #26 DEO #01 #2e DEO #02 #2e DEO BRK");
+    assert_eq!(pixel(&uxn, 0, 0), 1);
+    assert_eq!(pixel(&uxn, 1, 1), 2);
+    assert_eq!(uxn.devices.device_input_u16(0x28), 0x0002);
+    assert_eq!(uxn.devices.device_input_u16(0x2a), 0x0002);
+}
+
+#[test]
+fn sprite_auto_moves_the_position_and_address() {
+    let (mut uxn, assembly) = machine(
+        "|0100 #0010 #28 DEO2 #0010 #2a DEO2 ;sprite #2c DEO2 #05 #26 DEO #81 #2f DEO BRK
+         @sprite 80 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00",
+    );
+    assert_eq!(uxn.run().unwrap(), MachineEvent::Break);
+    assert_eq!(pixel(&uxn, 0x10, 0x10), 1);
+    assert_eq!(uxn.devices.device_input_u16(0x28), 0x18);
+    assert_eq!(uxn.devices.device_input_u16(0x2a), 0x10);
+    // Two bits per pixel sprites are 16 bytes long
+    let sprite = label(&assembly, "sprite");
+    assert_eq!(uxn.devices.device_input_u16(0x2c), sprite + 0x10);
+}
+
+#[test]
+fn repeated_sprites_go_across_the_auto_direction() {
+    // Moving along x draws the repeats down the screen, each from the next sprite
+    let mut uxn = draw(
+        "|0100 #0010 #28 DEO2 #0010 #2a DEO2 ;sprite #2c DEO2 #25 #26 DEO #01 #2f DEO BRK
+         @sprite 80 00 00 00 00 00 00 00 40 00 00 00 00 00 00 00 20 00 00 00 00 00 00 00",
+    );
+    assert_eq!(pixel(&uxn, 0x10, 0x10), 1);
+    assert_eq!(pixel(&uxn, 0x11, 0x18), 1);
+    assert_eq!(pixel(&uxn, 0x12, 0x20), 1);
+    assert_eq!(pixel(&uxn, 0x18, 0x10), 0);
+    assert_eq!(uxn.devices.device_input_u16(0x28), 0x18);
+    assert_eq!(uxn.devices.device_input_u16(0x2a), 0x10);
+}
+
+#[test]
+fn out_of_range_sizes_are_ignored() {
+    for size in [0x0004, 0x0800] {
+        let mut uxn = draw(&format!(
+            "|0100 #{size:04x} #22 DEO2 #{size:04x} #24 DEO2 BRK"
+        ));
+        assert_eq!(uxn.devices.screen().width(), 0x200);
+        assert_eq!(uxn.devices.screen().height(), 0x140);
+        assert_eq!(uxn.devices.device_input_u16(0x22), 0x200);
+    }
+    let uxn = draw("|0100 #0100 #22 DEO2 #0080 #24 DEO2 BRK");
+    assert_eq!(uxn.devices.screen().width(), 0x100);
+    assert_eq!(uxn.devices.screen().height(), 0x80);
+}
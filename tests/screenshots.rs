@@ -0,0 +1,73 @@
+mod common;
+
+use common::rom_machine;
+use std::{fs, io::Cursor, process::Command};
+use uxn::{Machine, MachineEvent};
+
+/// The screen ROM after `frames` frames.
+fn screen(frames: usize) -> Machine {
+    let mut uxn = rom_machine("roms/devices/screen.rom");
+    uxn.boot(&[] as &[&str]).unwrap();
+    for _ in 0..frames {
+        assert_eq!(uxn.screen_frame().unwrap(), MachineEvent::Break);
+    }
+    uxn
+}
+
+fn read_png(png: &[u8]) -> Vec<u8> {
+    let mut reader = png::Decoder::new(Cursor::new(png)).read_info().unwrap();
+    let mut rgb = vec![0; reader.output_buffer_size().unwrap()];
+    let info = reader.next_frame(&mut rgb).unwrap();
+    // The ROM sizes the screen to 240x160
+    assert_eq!((info.width, info.height), (240, 160));
+    assert_eq!(info.color_type, png::ColorType::Rgb);
+    rgb
+}
+
+#[test]
+fn ppm_is_a_header_then_the_rgb_bytes() {
+    let uxn = screen(30);
+    let mut ppm = vec![];
+    let palette = uxn.devices.palette();
+    uxn.devices.screen().write_ppm(&palette, &mut ppm).unwrap();
+    let header = b"P6\n240 160\n255\n";
+    assert_eq!(&ppm[..header.len()], header);
+    assert!(ppm[header.len()..] == uxn.devices.screen_rgb());
+}
+
+#[test]
+fn png_holds_the_rgb_bytes() {
+    let uxn = screen(30);
+    let mut png = vec![];
+    let palette = uxn.devices.palette();
+    uxn.devices.screen().write_png(&palette, &mut png).unwrap();
+    assert!(read_png(&png) == uxn.devices.screen_rgb());
+}
+
+#[test]
+fn screenshots_are_numbered_by_frame() {
+    let dir = std::env::temp_dir().join(format!("uxn-screenshots-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let status = Command::new(env!("CARGO_BIN_EXE_uxn"))
+        .args([
+            "--time",
+            "static",
+            "--frames",
+            "60",
+            "--screenshot-every",
+            "30",
+        ])
+        .arg("--screenshot")
+        .arg(dir.join("screen.png"))
+        .arg("roms/devices/screen.rom")
+        .stdin(std::process::Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let read = |name: &str| read_png(&fs::read(dir.join(name)).unwrap());
+    assert!(read("screen-00030.png") == screen(30).devices.screen_rgb());
+    assert!(read("screen-00060.png") == screen(60).devices.screen_rgb());
+    assert!(read("screen.png") == screen(60).devices.screen_rgb());
+    assert!(!dir.join("screen-00090.png").exists());
+    fs::remove_dir_all(dir).unwrap();
+}
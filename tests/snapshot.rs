@@ -0,0 +1,109 @@
+mod common;
+
+use common::{machine, rom_machine};
+use uxn::{Machine, MachineEvent, SnapshotError};
+
+/// Runs the screen vector `frames` times, returning the screen as it ends up.
+fn frames(uxn: &mut Machine, frames: usize) -> Vec<u8> {
+    for _ in 0..frames {
+        assert!(matches!(uxn.screen_frame().unwrap(), MachineEvent::Break));
+    }
+    uxn.devices.screen_rgb()
+}
+
+#[test]
+fn restored_machine_carries_on_the_same() {
+    let mut uxn = rom_machine("roms/devices/screen.rom");
+    uxn.boot(&[] as &[&str]).unwrap();
+    frames(&mut uxn, 30);
+    let snapshot = uxn.snapshot();
+    let expected = frames(&mut uxn, 30);
+    let memory = uxn.memory.peek_u8s(0x0000, 0xffff);
+
+    let mut restored = rom_machine("roms/devices/screen.rom");
+    restored.restore(&snapshot).unwrap();
+    assert!(frames(&mut restored, 30) == expected);
+    assert!(restored.memory.peek_u8s(0x0000, 0xffff) == memory);
+    assert_eq!(restored.cycles(), uxn.cycles());
+}
+
+#[test]
+fn snapshot_round_trips() {
+    let mut uxn = rom_machine("roms/devices/audio.rom");
+    uxn.boot(&[] as &[&str]).unwrap();
+    frames(&mut uxn, 20);
+    let snapshot = uxn.snapshot();
+    let mut restored = rom_machine("roms/devices/audio.rom");
+    restored.restore(&snapshot).unwrap();
+    assert!(restored.snapshot() == snapshot);
+}
+
+#[test]
+fn snapshot_leaves_out_the_file_system() {
+    let root = "/uxn-snapshot-test-root";
+    let mut uxn = rom_machine("roms/devices/file.rom");
+    uxn.devices.use_phycial_file_system(root, false);
+    let snapshot = uxn.snapshot();
+    let contains_root = snapshot
+        .windows(root.len())
+        .any(|window| window == root.as_bytes());
+    assert!(!contains_root);
+}
+
+#[test]
+fn bad_snapshots_are_rejected() {
+    let mut uxn = rom_machine("roms/devices/screen.rom");
+    assert!(matches!(
+        uxn.restore(b"not a snapshot"),
+        Err(SnapshotError::NotASnapshot)
+    ));
+    let snapshot = uxn.snapshot();
+    assert!(matches!(
+        uxn.restore(&snapshot[..snapshot.len() / 2]),
+        Err(SnapshotError::Corrupt)
+    ));
+    let mut newer = snapshot.clone();
+    newer[4..6].copy_from_slice(&u16::MAX.to_be_bytes());
+    assert!(matches!(
+        uxn.restore(&newer),
+        Err(SnapshotError::UnsupportedVersion(u16::MAX))
+    ));
+}
+
+#[test]
+fn screen_size_out_of_range_is_rejected() {
+    let mut uxn = rom_machine("roms/devices/screen.rom");
+    uxn.devices.resize_screen(0x0123, 0x0045);
+    let snapshot = uxn.snapshot();
+    // The Screen ports hold the size too, the screen itself is saved after them
+    let size = snapshot
+        .windows(4)
+        .rposition(|window| window == [0x01, 0x23, 0x00, 0x45])
+        .unwrap();
+    for width in [0x0000, 0xffff] {
+        let mut bad = snapshot.clone();
+        bad[size..size + 2].copy_from_slice(&u16::to_be_bytes(width));
+        assert!(matches!(uxn.restore(&bad), Err(SnapshotError::Corrupt)));
+    }
+    uxn.restore(&snapshot).unwrap();
+}
+
+#[test]
+fn halted_machine_stays_halted() {
+    let (mut uxn, _) = machine("|0100 #01 #0f DEO #aa #18 DEO BRK");
+    assert_eq!(uxn.run().unwrap(), MachineEvent::Halt(0x01));
+    let mut restored = Machine::new();
+    restored.restore(&uxn.snapshot()).unwrap();
+    assert_eq!(restored.devices.halt_state(), Some(0x01));
+}
+
+#[test]
+fn restored_fault_happens_again() {
+    let (mut uxn, _) = machine("|0100 #0102 #03 ADD2 #aa #18 DEO BRK");
+    let error = uxn.run().unwrap_err();
+    let mut restored = Machine::new();
+    restored.restore(&uxn.snapshot()).unwrap();
+    let replayed = restored.run().unwrap_err();
+    assert_eq!(replayed.addr, error.addr);
+    assert_eq!(replayed.wk_stack, error.wk_stack);
+}
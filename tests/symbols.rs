@@ -0,0 +1,72 @@
+use uxn::{assemble_source, Label, Symbols};
+
+const SOURCE: &str = "|0000 @zp $2 |0100 @main #01 POP &loop ,&loop JMP @data 01 02";
+
+fn symbols() -> Symbols {
+    Symbols::from_bytes(&assemble_source(SOURCE, "test.tal").unwrap().symbol_file())
+}
+
+fn label(name: &str, addr: u16) -> Label {
+    let name = name.to_string();
+    Label { name, addr }
+}
+
+#[test]
+fn symbol_file_round_trips() {
+    let assembly = assemble_source(SOURCE, "test.tal").unwrap();
+    let symbols = Symbols::from_bytes(&assembly.symbol_file());
+    assert_eq!(symbols.labels(), assembly.labels);
+    assert_eq!(symbols.addr("main/loop"), Some(0x0103));
+    assert_eq!(symbols.name(0x0106), Some("data"));
+}
+
+#[test]
+fn truncated_last_record_is_ignored() {
+    let assembly = assemble_source(SOURCE, "test.tal").unwrap();
+    for tail in [&[0x02][..], &[0x02, 0x00], &[0x02, 0x00, b'c', b'u', b't']] {
+        let mut bytes = assembly.symbol_file();
+        bytes.extend_from_slice(tail);
+        assert_eq!(Symbols::from_bytes(&bytes).labels(), assembly.labels);
+    }
+}
+
+#[test]
+fn locations_are_labels_plus_hex_offsets() {
+    let symbols = symbols();
+    assert_eq!(symbols.location(0x0100).as_deref(), Some("main"));
+    assert_eq!(symbols.location(0x0104).as_deref(), Some("main/loop+1"));
+    assert_eq!(symbols.location(0x0001).as_deref(), Some("zp+1"));
+    assert_eq!(symbols.location(0x00ff).as_deref(), Some("zp+ff"));
+    assert_eq!(symbols.locate(0x0108), Some(("data", 2)));
+    assert_eq!(symbols.describe(0x0104), "0104 main/loop+1");
+}
+
+#[test]
+fn zero_page_labels_stay_in_the_zero_page() {
+    let symbols = Symbols::from(vec![label("zp", 0x0000), label("main", 0x0200)]);
+    assert_eq!(symbols.location(0x0080).as_deref(), Some("zp+80"));
+    assert_eq!(symbols.location(0x0150), None);
+    assert_eq!(symbols.describe(0x0150), "0150");
+    assert_eq!(symbols.location(0x0201).as_deref(), Some("main+1"));
+}
+
+#[test]
+fn addresses_parse_as_labels_offsets_or_hex() {
+    let symbols = symbols();
+    assert_eq!(symbols.parse_addr("main/loop"), Some(0x0103));
+    assert_eq!(symbols.parse_addr("main/loop+2"), Some(0x0105));
+    assert_eq!(symbols.parse_addr("main+0x10"), Some(0x0110));
+    assert_eq!(symbols.parse_addr("ff"), Some(0x00ff));
+    assert_eq!(symbols.parse_addr("0x1234"), Some(0x1234));
+    assert_eq!(symbols.parse_addr("nowhere+1"), None);
+    assert_eq!(symbols.parse_addr("main+zz"), None);
+    assert_eq!(symbols.parse_addr("nowhere"), None);
+}
+
+#[test]
+fn label_shadows_hex() {
+    let symbols = Symbols::from(vec![label("beef", 0x0200)]);
+    assert_eq!(symbols.parse_addr("beef"), Some(0x0200));
+    assert_eq!(symbols.parse_addr("beef+1"), Some(0x0201));
+    assert_eq!(symbols.parse_addr("bee"), Some(0x0bee));
+}
@@ -0,0 +1,26 @@
+mod common;
+
+use common::machine;
+use uxn::{MachineEvent, Tracer};
+
+#[test]
+fn ring_buffer_keeps_the_last_instructions() {
+    let (mut uxn, _) = machine("|0100 #01 #02 #03 ADD POP #04 BRK");
+    uxn.tracer = Some(Tracer::ring_buffer(std::io::sink(), 3));
+    assert_eq!(uxn.run().unwrap(), MachineEvent::Break);
+    let entries = uxn
+        .tracer
+        .as_ref()
+        .unwrap()
+        .entries()
+        .map(|entry| (entry.op_code().to_string(), entry.wk_stack.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        entries,
+        [
+            ("POP".to_string(), vec![0x01]),
+            ("LIT".to_string(), vec![0x01, 0x04]),
+            ("BRK".to_string(), vec![0x01, 0x04]),
+        ]
+    );
+}